@@ -1,36 +1,89 @@
+use crate::utils::matrix::Matrix;
+use crate::utils::modular::{Modular, PRIME};
 use crate::utils::SolverResult;
 use itertools::Itertools;
+use num::{BigUint, One, Zero};
 use std::fs::read_to_string;
+use std::num::ParseIntError;
+use std::ops::Mul;
 
-fn track(fish: &[usize], days: usize) -> usize {
-    let mut counts = [0usize; 9];
+// How long a lanternfish waits to spawn again, and how long a newborn waits to spawn
+// for the first time.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Lifecycle {
+    pub reset: usize,
+    pub newborn: usize,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle {
+            reset: 6,
+            newborn: 8,
+        }
+    }
+}
 
-    fish.iter().for_each(|f| counts[*f] += 1);
+// How many fish there are with each timer value the next day, as a matrix acting on the
+// counts for today. Entry (to, from) is how many fish with timer `to` one with timer `from` becomes.
+fn transition<T: Zero + One>(lifecycle: &Lifecycle, timers: usize) -> Matrix<T> {
+    Matrix::from_fn(timers, |to, from| {
+        if from == 0 {
+            // Spawning resets the timer and also creates a new fish.
+            [lifecycle.reset, lifecycle.newborn]
+                .iter()
+                .filter(|&&timer| timer == to)
+                .fold(T::zero(), |total, _| total + T::one())
+        } else if to + 1 == from {
+            T::one()
+        } else {
+            T::zero()
+        }
+    })
+}
+
+// The number of fish after the given number of days.
+// Generic so that huge populations can be counted with a BigUint (or modulo a prime, with Modular).
+pub fn population<T>(fish: &[usize], lifecycle: &Lifecycle, days: u64) -> T
+where
+    T: Clone + Zero + One,
+    for<'a> &'a T: Mul<Output = T>,
+{
+    let timers = fish
+        .iter()
+        .chain([lifecycle.reset, lifecycle.newborn].iter())
+        .max()
+        .unwrap()
+        + 1;
 
-    (0..days).for_each(|_day| {
-        counts.rotate_left(1);
-        // the counts that were in 0 are now in 8 (they now represent the new fish),
-        // and should also be added to 6 (the old fish, now reset).
-        counts[6] += counts[8];
-    });
+    let counts = fish.iter().counts();
+    let initial = (0..timers)
+        .map(|timer| {
+            (0..counts.get(&timer).copied().unwrap_or(0))
+                .fold(T::zero(), |total, _| total + T::one())
+        })
+        .collect_vec();
 
-    counts.iter().sum()
+    transition::<T>(lifecycle, timers)
+        .pow_apply(days, &initial)
+        .into_iter()
+        .fold(T::zero(), |total, count| total + count)
 }
 
 fn part_1(fish: &[usize]) -> usize {
-    track(fish, 80)
+    population::<usize>(fish, &Lifecycle::default(), 80)
 }
 
 fn part_2(fish: &[usize]) -> usize {
-    track(fish, 256)
+    population::<usize>(fish, &Lifecycle::default(), 256)
+}
+
+fn parse_input(input: &str) -> Result<Vec<usize>, ParseIntError> {
+    input.trim().split(',').map(&str::parse).try_collect()
 }
 
 pub fn solve() -> SolverResult {
-    let fish: Vec<usize> = read_to_string("data/day_06.txt")?
-        .trim()
-        .split(',')
-        .map(&str::parse)
-        .try_collect()?;
+    let fish = parse_input(&read_to_string("data/day_06.txt")?)?;
 
     println!("Part 1: {}", part_1(&fish));
     println!("Part 2: {}", part_2(&fish));
@@ -38,6 +91,27 @@ pub fn solve() -> SolverResult {
     Ok(())
 }
 
+// Counts the fish after any number of days with any lifecycle, exactly or modulo a prime.
+pub fn solve_with(lifecycle: &Lifecycle, days: u64, modulo: bool) -> SolverResult {
+    let fish = parse_input(&read_to_string("data/day_06.txt")?)?;
+
+    if modulo {
+        println!(
+            "Fish after {} days: {}",
+            days,
+            population::<Modular<PRIME>>(&fish, lifecycle, days)
+        );
+    } else {
+        println!(
+            "Fish after {} days: {}",
+            days,
+            population::<BigUint>(&fish, lifecycle, days)
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,4 +127,80 @@ mod tests {
     fn part_2_examples() {
         assert_eq!(part_2(&FISH), 26984457539);
     }
+
+    // The original fixed-size simulation, to check the matrix version against.
+    fn track(fish: &[usize], days: usize) -> usize {
+        let mut counts = [0usize; 9];
+
+        fish.iter().for_each(|f| counts[*f] += 1);
+
+        (0..days).for_each(|_day| {
+            counts.rotate_left(1);
+            // the counts that were in 0 are now in 8 (they now represent the new fish),
+            // and should also be added to 6 (the old fish, now reset).
+            counts[6] += counts[8];
+        });
+
+        counts.iter().sum()
+    }
+
+    #[test]
+    fn matches_simulation() {
+        [0, 1, 18, 80, 256].iter().for_each(|&days| {
+            assert_eq!(
+                population::<usize>(&FISH, &Lifecycle::default(), days as u64),
+                track(&FISH, days)
+            );
+        });
+        assert_eq!(population::<usize>(&FISH, &Lifecycle::default(), 18), 26);
+    }
+
+    #[test]
+    fn other_lifecycles() {
+        // Fish that spawn every day double every day, once they're all due to spawn
+        let doubling = Lifecycle {
+            reset: 0,
+            newborn: 0,
+        };
+        assert_eq!(population::<usize>(&[0, 0, 0], &doubling, 10), 3 << 10);
+
+        // When newborns start on the same timer as their parents, everyone spawns together
+        let lifecycle = Lifecycle {
+            reset: 2,
+            newborn: 2,
+        };
+        assert_eq!(population::<usize>(&[0], &lifecycle, 3), 2);
+        assert_eq!(population::<usize>(&[0], &lifecycle, 4), 4);
+
+        // Fish can start with timers longer than either lifecycle timer
+        assert_eq!(population::<usize>(&[12], &Lifecycle::default(), 12), 1);
+        assert_eq!(population::<usize>(&[12], &Lifecycle::default(), 13), 2);
+    }
+
+    #[test]
+    fn huge_day_counts() {
+        let lifecycle = Lifecycle::default();
+
+        // usize overflows a little past day 256...
+        let exact = population::<BigUint>(&FISH, &lifecycle, 10_000);
+        assert!(exact > BigUint::from(usize::MAX));
+        assert_eq!(
+            population::<BigUint>(&FISH, &lifecycle, 256),
+            BigUint::from(26984457539u64)
+        );
+
+        // ... but counting modulo a prime agrees with the exact count, and keeps going
+        assert_eq!(
+            population::<Modular<PRIME>>(&FISH, &lifecycle, 10_000).value(),
+            (exact % PRIME)
+                .to_u64_digits()
+                .first()
+                .copied()
+                .unwrap_or(0)
+        );
+        assert_eq!(
+            population::<Modular<PRIME>>(&FISH, &lifecycle, 1_000_000_000).value(),
+            249911329
+        );
+    }
 }
@@ -1,61 +1,43 @@
+use crate::utils::grid::{Edges, Grid, ORTHOGONAL};
 use crate::utils::SolverResult;
 use itertools::Itertools;
-use ndarray::{s, Array2};
 use std::collections::{BinaryHeap, HashMap};
 use std::fs::read_to_string;
 
-fn part_1(heights: &Array2<usize>) -> usize {
+fn part_1(heights: &Grid<u8>) -> usize {
     heights
-        .windows((3, 3))
-        .into_iter()
-        .filter_map(|window| {
-            let center = window[(1, 1)];
-            let edges = window
-                .iter()
-                .enumerate()
-                .filter_map(|(idx, element)| (idx % 2 == 1).then_some(element))
-                .cloned()
-                .collect_vec();
-            edges.iter().all(|&o| o > center).then_some(center + 1)
+        .iter()
+        .filter(|&(p, &center)| {
+            heights
+                .neighbours(p, &ORTHOGONAL, Edges::Bounded)
+                .all(|n| heights[n] > center)
         })
+        .map(|(_, &center)| center as usize + 1)
         .sum()
 }
 
-fn part_2(heights: &Array2<usize>) -> usize {
+fn part_2(heights: &Grid<u8>) -> usize {
     let mut new_basin: usize = 0;
     let mut basins = HashMap::new();
 
-    heights.indexed_iter().for_each(|((x, y), h)| {
-        if h >= &9 {
+    heights.iter().for_each(|(p, &h)| {
+        if h >= 9 {
             return;
         }
 
-        if basins.contains_key(&(x, y)) {
+        if basins.contains_key(&p) {
             return;
         }
 
-        let mut candidates = vec![(x, y)];
-        while !candidates.is_empty() {
-            let (x, y) = candidates.pop().unwrap();
-
-            if heights[[x, y]] >= 9 {
+        let mut candidates = vec![p];
+        while let Some(p) = candidates.pop() {
+            if heights[p] >= 9 {
                 continue;
             }
 
-            basins.insert((x, y), new_basin);
+            basins.insert(p, new_basin);
 
-            if let Some(new_x) = x.checked_sub(1) {
-                candidates.push((new_x, y));
-            }
-            if let Some(new_y) = y.checked_sub(1) {
-                candidates.push((x, new_y));
-            }
-            if (x + 1) < heights.shape()[0] {
-                candidates.push((x + 1, y));
-            }
-            if (y + 1) < heights.shape()[1] {
-                candidates.push((x, y + 1));
-            }
+            candidates.extend(heights.neighbours(p, &ORTHOGONAL, Edges::Bounded));
 
             candidates.retain(|xy| !basins.contains_key(xy));
         }
@@ -74,24 +56,8 @@ fn part_2(heights: &Array2<usize>) -> usize {
         .product()
 }
 
-fn parse_input(input: &str) -> Array2<usize> {
-    let h = input.lines().next().unwrap().len();
-    let w = input.lines().collect_vec().len();
-
-    let heights = Array2::from_shape_vec(
-        (w, h),
-        input
-            .lines()
-            .flat_map(|line| line.chars())
-            .map(|c| c.to_digit(10).unwrap() as usize)
-            .collect(),
-    )
-    .unwrap();
-
-    let mut outer: Array2<usize> = Array2::ones((w + 2, h + 2)) * 10;
-    outer.slice_mut(s![1..=w, 1..=h]).assign(&heights);
-
-    outer
+fn parse_input(input: &str) -> Grid<u8> {
+    Grid::parse_digits(input)
 }
 
 pub fn solve() -> SolverResult {
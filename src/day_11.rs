@@ -1,28 +1,10 @@
+use crate::utils::grid::{Edges, Grid, Position, SURROUNDING};
 use crate::utils::SolverResult;
-use std::collections::{HashMap, HashSet};
+use itertools::Itertools;
+use std::collections::HashSet;
 use std::fs::read_to_string;
 
-type Position = (isize, isize);
-type Levels = HashMap<Position, u8>;
-
-fn neighbours(p: &Position) -> Vec<Position> {
-    let (x, y) = p;
-    let x_m = x - 1;
-    let x_p = x + 1;
-    let y_m = y - 1;
-    let y_p = y + 1;
-
-    vec![
-        (x_m, y_m),
-        (x_m, *y),
-        (x_m, y_p),
-        (*x, y_m),
-        (*x, y_p),
-        (x_p, y_m),
-        (x_p, *y),
-        (x_p, y_p),
-    ]
-}
+type Levels = Grid<u8>;
 
 fn find_flashers(levels: &mut Levels) -> HashSet<Position> {
     levels.values_mut().for_each(|level| *level += 1);
@@ -30,25 +12,25 @@ fn find_flashers(levels: &mut Levels) -> HashSet<Position> {
     let mut flashed = HashSet::new();
     while levels
         .iter()
-        .any(|(p, &level)| level > 9 && !flashed.contains(p))
+        .any(|(p, &level)| level > 9 && !flashed.contains(&p))
     {
         levels.clone().iter().for_each(|(p, &level)| {
-            if level <= 9 || flashed.contains(p) {
+            if level <= 9 || flashed.contains(&p) {
                 return;
             }
 
-            flashed.insert(*p);
+            flashed.insert(p);
 
-            neighbours(p).iter().for_each(|neighbour| {
-                if let Some(l) = levels.get_mut(neighbour) {
-                    *l += 1
-                }
-            })
+            levels
+                .neighbours(p, &SURROUNDING, Edges::Bounded)
+                .collect_vec()
+                .into_iter()
+                .for_each(|neighbour| levels[neighbour] += 1)
         })
     }
 
-    flashed.iter().for_each(|p| {
-        levels.insert(*p, 0);
+    flashed.iter().for_each(|&p| {
+        levels[p] = 0;
     });
 
     flashed
@@ -73,18 +55,7 @@ fn part_2(levels: &Levels) -> isize {
 }
 
 fn parse_input(input: &str) -> Levels {
-    input
-        .lines()
-        .enumerate()
-        .flat_map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(move |(x, level)| (x, y, level.to_digit(10).unwrap() as u8))
-        })
-        .fold(HashMap::new(), move |mut levels, (x, y, level)| {
-            levels.insert((x as isize, y as isize), level);
-            levels
-        })
+    Grid::parse_digits(input)
 }
 
 pub fn solve() -> SolverResult {
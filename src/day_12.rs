@@ -1,65 +1,336 @@
 use crate::utils::SolverResult;
 use itertools::Itertools;
-use std::collections::HashMap;
-use std::fs::read_to_string;
+use std::collections::{HashMap, HashSet};
+use std::fs::{read_to_string, write};
 
 type Node = String;
 type Edges = HashMap<Node, Vec<Node>>;
-type Path = Vec<Node>;
 
 fn is_small(node: &str) -> bool {
     node.to_ascii_lowercase() == node
 }
 
-fn find_paths(edges: &Edges, path: Path, is_path_good: fn(path: &Path) -> bool) -> Vec<Path> {
-    if let Some(nexts) = edges.get(path.last().unwrap()) {
-        nexts
+// Which small caves a path may go back into.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Policy {
+    // How many different small caves may be visited more than once.
+    pub revisits: usize,
+    // How many times each of those caves may be visited in total.
+    pub max_visits: usize,
+    // Small caves that may only ever be visited once; start is always one of them.
+    pub forbidden: HashSet<String>,
+}
+
+impl Policy {
+    fn part_1() -> Self {
+        Policy::default()
+    }
+
+    fn part_2() -> Self {
+        Policy {
+            revisits: 1,
+            max_visits: 2,
+            ..Policy::default()
+        }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            revisits: 0,
+            max_visits: 1,
+            forbidden: HashSet::new(),
+        }
+    }
+}
+
+// Visit counts for every small cave, packed into 4 bits each by the cave's rank
+// among the small caves. Big caves can be visited any number of times, so they
+// aren't counted.
+type Visits = u128;
+
+const VISIT_BITS: usize = 4;
+
+fn visits(visits: Visits, rank: usize) -> usize {
+    (visits >> (rank * VISIT_BITS)) as usize & ((1 << VISIT_BITS) - 1)
+}
+
+fn visit(visits: Visits, rank: usize) -> Visits {
+    visits + (1 << (rank * VISIT_BITS))
+}
+
+// The cave system with each cave interned as an index, so the visit counts for
+// a path fit in a single integer.
+struct Caves<'a> {
+    names: Vec<&'a str>,
+    neighbours: Vec<Vec<usize>>,
+    // The rank of each small cave among the small caves, and None for big caves
+    ranks: Vec<Option<usize>>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Caves<'a> {
+    fn new(edges: &'a Edges) -> Self {
+        let names = edges
+            .keys()
+            .map(|name| name.as_str())
+            .sorted()
+            .collect_vec();
+        let small = names.iter().filter(|name| is_small(name)).count();
+        assert!(
+            small * VISIT_BITS <= Visits::BITS as usize,
+            "too many small caves to pack their visit counts"
+        );
+
+        let index = |name: &str| names.iter().position(|&n| n == name).unwrap();
+
+        Caves {
+            neighbours: names
+                .iter()
+                .map(|&name| edges[name].iter().map(|n| index(n)).collect())
+                .collect(),
+            ranks: names
+                .iter()
+                .scan(0, |rank, name| {
+                    Some(is_small(name).then(|| {
+                        *rank += 1;
+                        *rank - 1
+                    }))
+                })
+                .collect(),
+            start: index("start"),
+            end: index("end"),
+            names,
+        }
+    }
+
+    // The visit counts of a path that has only just left the start.
+    fn starting_visits(&self) -> Visits {
+        visit(0, self.ranks[self.start].unwrap())
+    }
+
+    // Small caves that the policy lets us visit only once, by rank.
+    fn once(&self, policy: &Policy) -> Visits {
+        self.names
             .iter()
-            .flat_map(|next| {
-                if next == "end" {
-                    let mut new_path: Path = path.clone();
-                    new_path.push(next.clone());
-                    vec![new_path]
-                } else {
-                    let mut new_path: Path = path.clone();
-                    new_path.push(next.clone());
-                    if is_path_good(&new_path) {
-                        find_paths(edges, new_path, is_path_good)
-                    } else {
-                        vec![]
-                    }
-                }
-            })
-            .collect()
+            .zip(&self.ranks)
+            .filter(|(name, _)| **name == "start" || policy.forbidden.contains(**name))
+            .filter_map(|(_, rank)| *rank)
+            .fold(0, |once, rank| once | 1 << rank)
+    }
+}
+
+// Everything that decides whether a path can continue into a cave.
+struct Rules<'a> {
+    caves: &'a Caves<'a>,
+    once: Visits,
+    revisits: usize,
+    max_visits: usize,
+}
+
+impl<'a> Rules<'a> {
+    fn new(caves: &'a Caves, policy: &Policy) -> Self {
+        assert!(
+            policy.max_visits < 1 << VISIT_BITS,
+            "too many visits to pack into the visit counts"
+        );
+
+        Rules {
+            caves,
+            once: caves.once(policy),
+            revisits: policy.revisits,
+            max_visits: policy.max_visits,
+        }
+    }
+
+    // The visit counts after moving into next, if that's allowed.
+    fn enter(&self, visited: Visits, revisited: usize, next: usize) -> Option<(Visits, usize)> {
+        // Big caves can always be entered.
+        let rank = match self.caves.ranks[next] {
+            Some(rank) => rank,
+            None => return Some((visited, revisited)),
+        };
+
+        match visits(visited, rank) {
+            0 => Some((visit(visited, rank), revisited)),
+            _ if self.once & 1 << rank != 0 => None,
+            n if n >= self.max_visits => None,
+            1 if revisited >= self.revisits => None,
+            1 => Some((visit(visited, rank), revisited + 1)),
+            _ => Some((visit(visited, rank), revisited)),
+        }
+    }
+}
+
+type Memo = HashMap<(usize, Visits), usize>;
+
+// Counts the paths to the end from cave, given how many times each cave has been
+// visited and how many small caves have been revisited so far.
+fn _count_paths(
+    rules: &Rules,
+    cave: usize,
+    visited: Visits,
+    revisited: usize,
+    memo: &mut Memo,
+) -> usize {
+    if cave == rules.caves.end {
+        return 1;
+    }
+    // The number of revisits is determined by the visit counts, so it doesn't need to be in the key.
+    if let Some(&count) = memo.get(&(cave, visited)) {
+        return count;
+    }
+
+    let count = rules.caves.neighbours[cave]
+        .iter()
+        .filter_map(|&next| {
+            rules
+                .enter(visited, revisited, next)
+                .map(|(visited, revisited)| _count_paths(rules, next, visited, revisited, memo))
+        })
+        .sum();
+
+    memo.insert((cave, visited), count);
+
+    count
+}
+
+fn count_paths(edges: &Edges, policy: &Policy) -> usize {
+    let caves = Caves::new(edges);
+    let rules = Rules::new(&caves, policy);
+
+    _count_paths(
+        &rules,
+        caves.start,
+        caves.starting_visits(),
+        0,
+        &mut Memo::new(),
+    )
+}
+
+// Calls on_path with every path from the end of path to the end cave.
+fn _walk_paths(
+    rules: &Rules,
+    path: &mut Vec<usize>,
+    visited: Visits,
+    revisited: usize,
+    on_path: &mut impl FnMut(&[usize]),
+) {
+    let cave = *path.last().unwrap();
+
+    if cave == rules.caves.end {
+        on_path(path);
+        return;
+    }
+
+    rules.caves.neighbours[cave].iter().for_each(|&next| {
+        if let Some((visited, revisited)) = rules.enter(visited, revisited, next) {
+            path.push(next);
+            _walk_paths(rules, path, visited, revisited, on_path);
+            path.pop();
+        }
+    });
+}
+
+fn walk_paths(edges: &Edges, policy: &Policy, mut on_path: impl FnMut(&[&str])) {
+    let caves = Caves::new(edges);
+    let rules = Rules::new(&caves, policy);
+
+    _walk_paths(
+        &rules,
+        &mut vec![caves.start],
+        caves.starting_visits(),
+        0,
+        &mut |path| on_path(&path.iter().map(|&c| caves.names[c]).collect_vec()),
+    );
+}
+
+// Every path allowed by the policy, written like start,A,b,end, in sorted order.
+fn list_paths(edges: &Edges, policy: &Policy) -> Vec<String> {
+    let mut paths = vec![];
+    walk_paths(edges, policy, |path| paths.push(path.join(",")));
+
+    paths.sort();
+    paths
+}
+
+// How many times each tunnel (named by its caves in sorted order) is used, over
+// all the paths allowed by the policy.
+type Usage = HashMap<(String, String), usize>;
+
+fn tunnel(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_owned(), b.to_owned())
     } else {
-        vec![]
+        (b.to_owned(), a.to_owned())
     }
 }
 
+fn tunnel_usage(edges: &Edges, policy: &Policy) -> Usage {
+    let mut usage = Usage::new();
+
+    walk_paths(edges, policy, |path| {
+        path.array_windows()
+            .for_each(|[a, b]| *usage.entry(tunnel(a, b)).or_default() += 1)
+    });
+
+    usage
+}
+
+// The cave system in Graphviz DOT format: big caves are boxes, small caves are
+// ellipses, and start and end are filled in. With usage, tunnels are labelled
+// with how often they're used and drawn thicker the more they are.
+fn to_dot(edges: &Edges, usage: Option<&Usage>) -> String {
+    let caves = edges
+        .keys()
+        .sorted()
+        .map(|cave| {
+            let attributes = match cave.as_str() {
+                "start" => "shape=doublecircle, style=filled, fillcolor=palegreen",
+                "end" => "shape=doublecircle, style=filled, fillcolor=lightcoral",
+                c if is_small(c) => "shape=ellipse",
+                _ => "shape=box, style=bold",
+            };
+            format!("    \"{}\" [{}];", cave, attributes)
+        })
+        .join("\n");
+
+    let most_used = usage.and_then(|u| u.values().max().copied()).unwrap_or(0);
+
+    let tunnels = edges
+        .iter()
+        .flat_map(|(a, bs)| bs.iter().map(move |b| tunnel(a, b)))
+        .unique()
+        .sorted()
+        .map(|(a, b)| match usage {
+            Some(usage) => {
+                let used = usage.get(&(a.clone(), b.clone())).copied().unwrap_or(0);
+                let width = 1.0 + 4.0 * used as f64 / most_used.max(1) as f64;
+
+                format!(
+                    "    \"{}\" -- \"{}\" [label={}, penwidth={:.1}{}];",
+                    a,
+                    b,
+                    used,
+                    width,
+                    if used == 0 { ", style=dashed" } else { "" }
+                )
+            }
+            None => format!("    \"{}\" -- \"{}\";", a, b),
+        })
+        .join("\n");
+
+    format!("graph caves {{\n{}\n\n{}\n}}\n", caves, tunnels)
+}
+
 fn part_1(edges: &Edges) -> usize {
-    find_paths(edges, vec!["start".to_owned()], |path| {
-        path.iter()
-            .filter(|node| is_small(node))
-            .counts()
-            .values()
-            .all(|count| count == &1)
-    })
-    .len()
+    count_paths(edges, &Policy::part_1())
 }
 
 fn part_2(edges: &Edges) -> usize {
-    find_paths(edges, vec!["start".to_owned()], |path| {
-        let node_visit_counts = path.iter().filter(|node| is_small(node)).counts();
-
-        (path.last().unwrap() != "start")
-            && (node_visit_counts.values().max().unwrap() <= &2)
-            && (node_visit_counts
-                .values()
-                .filter(|&count| count > &1)
-                .count()
-                <= 1)
-    })
-    .len()
+    count_paths(edges, &Policy::part_2())
 }
 
 fn parse_input(input: &str) -> Edges {
@@ -88,10 +359,95 @@ pub fn solve() -> SolverResult {
     Ok(())
 }
 
+// Counts (and optionally lists) the paths allowed by a custom policy,
+// optionally writing out the cave system with how much each tunnel is used by them.
+pub fn solve_with(policy: &Policy, list: bool, dot: bool) -> SolverResult {
+    let edges = parse_input(&read_to_string("data/day_12.txt")?);
+
+    if let Some(cave) = policy
+        .forbidden
+        .iter()
+        .find(|&cave| !edges.contains_key(cave))
+    {
+        return Err(format!("there's no cave named {}", cave).into());
+    }
+
+    if list {
+        list_paths(&edges, policy)
+            .iter()
+            .for_each(|path| println!("{}", path));
+    }
+    println!("Paths: {}", count_paths(&edges, policy));
+
+    if dot {
+        write(
+            "data/day_12.dot",
+            to_dot(&edges, Some(&tunnel_usage(&edges, policy))),
+        )?;
+        println!("Wrote the cave system to data/day_12.dot");
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    type Path = Vec<Node>;
+
+    // The original solution, which builds every path; kept to check the counts against.
+    fn find_paths(edges: &Edges, path: Path, is_path_good: fn(path: &Path) -> bool) -> Vec<Path> {
+        if let Some(nexts) = edges.get(path.last().unwrap()) {
+            nexts
+                .iter()
+                .flat_map(|next| {
+                    if next == "end" {
+                        let mut new_path: Path = path.clone();
+                        new_path.push(next.clone());
+                        vec![new_path]
+                    } else {
+                        let mut new_path: Path = path.clone();
+                        new_path.push(next.clone());
+                        if is_path_good(&new_path) {
+                            find_paths(edges, new_path, is_path_good)
+                        } else {
+                            vec![]
+                        }
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn enumerate_part_1(edges: &Edges) -> usize {
+        find_paths(edges, vec!["start".to_owned()], |path| {
+            path.iter()
+                .filter(|node| is_small(node))
+                .counts()
+                .values()
+                .all(|count| count == &1)
+        })
+        .len()
+    }
+
+    fn enumerate_part_2(edges: &Edges) -> usize {
+        find_paths(edges, vec!["start".to_owned()], |path| {
+            let node_visit_counts = path.iter().filter(|node| is_small(node)).counts();
+
+            (path.last().unwrap() != "start")
+                && (node_visit_counts.values().max().unwrap() <= &2)
+                && (node_visit_counts
+                    .values()
+                    .filter(|&count| count > &1)
+                    .count()
+                    <= 1)
+        })
+        .len()
+    }
+
     const SMALL_INPUT: &str = "\
 start-A
 start-b
@@ -131,6 +487,154 @@ zg-he
 pj-fs
 start-RW";
 
+    #[test]
+    fn counts_match_enumeration() {
+        [SMALL_INPUT, MEDIUM_INPUT, LARGE_INPUT]
+            .iter()
+            .for_each(|input| {
+                let edges = parse_input(input);
+                assert_eq!(part_1(&edges), enumerate_part_1(&edges));
+                assert_eq!(part_2(&edges), enumerate_part_2(&edges));
+            });
+    }
+
+    #[test]
+    fn many_caves() {
+        // 30 small caves besides start and end fill every bit of the visit counts,
+        // and the 40 big caves don't take up any.
+        let input = (0..30)
+            .map(|i| format!("c{}", i))
+            .chain((0..40).map(|i| format!("B{}", i)))
+            .flat_map(|cave| [format!("start-{}", cave), format!("{}-end", cave)])
+            .chain(["B39-c29".to_owned(), "B0-c0".to_owned()])
+            .join("\n");
+        let edges = parse_input(&input);
+
+        assert_eq!(edges.len(), 72);
+        assert_eq!(part_1(&edges), enumerate_part_1(&edges));
+        assert_eq!(part_2(&edges), enumerate_part_2(&edges));
+    }
+
+    #[test]
+    fn list_examples() {
+        assert_eq!(
+            list_paths(&parse_input(SMALL_INPUT), &Policy::part_1()),
+            vec![
+                "start,A,b,A,c,A,end",
+                "start,A,b,A,end",
+                "start,A,b,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,A,end",
+                "start,b,A,c,A,end",
+                "start,b,A,end",
+                "start,b,end",
+            ]
+        );
+
+        [SMALL_INPUT, MEDIUM_INPUT, LARGE_INPUT]
+            .iter()
+            .for_each(|input| {
+                let edges = parse_input(input);
+                [Policy::part_1(), Policy::part_2()]
+                    .iter()
+                    .for_each(|policy| {
+                        let paths = list_paths(&edges, policy);
+                        assert_eq!(paths.len(), count_paths(&edges, policy));
+                        assert_eq!(paths.iter().unique().count(), paths.len());
+                    });
+            });
+    }
+
+    #[test]
+    fn custom_policies() {
+        let edges = parse_input(SMALL_INPUT);
+
+        // Never revisiting b leaves only the paths that revisit c (or nothing)
+        let policy = Policy {
+            forbidden: HashSet::from(["b".to_owned()]),
+            ..Policy::part_2()
+        };
+        let paths = list_paths(&edges, &policy);
+        assert!(paths.iter().all(|path| path.matches('b').count() <= 1));
+        assert!(paths.contains(&"start,A,c,A,c,A,end".to_owned()));
+        assert_eq!(paths.len(), count_paths(&edges, &policy));
+        assert_eq!(
+            count_paths(&edges, &policy),
+            count_paths(&edges, &Policy::part_2())
+                - list_paths(&edges, &Policy::part_2())
+                    .iter()
+                    .filter(|path| path.matches('b').count() > 1)
+                    .count()
+        );
+
+        // Loosening the policy only ever adds paths
+        [SMALL_INPUT, MEDIUM_INPUT, LARGE_INPUT]
+            .iter()
+            .for_each(|input| {
+                let edges = parse_input(input);
+                let counts = [(0, 1), (1, 2), (1, 3), (2, 2), (2, 3)]
+                    .iter()
+                    .map(|&(revisits, max_visits)| {
+                        let policy = Policy {
+                            revisits,
+                            max_visits,
+                            ..Policy::default()
+                        };
+                        let count = count_paths(&edges, &policy);
+                        if count < 10000 {
+                            assert_eq!(list_paths(&edges, &policy).len(), count);
+                        }
+                        count
+                    })
+                    .collect_vec();
+
+                assert!(counts[0] <= counts[1] && counts[1] <= counts[2] && counts[2] <= counts[4]);
+                assert!(counts[1] <= counts[3] && counts[3] <= counts[4]);
+            });
+    }
+
+    #[test]
+    fn dot_export() {
+        let edges = parse_input(SMALL_INPUT);
+
+        assert_eq!(
+            to_dot(&edges, None),
+            r#"graph caves {
+    "A" [shape=box, style=bold];
+    "b" [shape=ellipse];
+    "c" [shape=ellipse];
+    "d" [shape=ellipse];
+    "end" [shape=doublecircle, style=filled, fillcolor=lightcoral];
+    "start" [shape=doublecircle, style=filled, fillcolor=palegreen];
+
+    "A" -- "b";
+    "A" -- "c";
+    "A" -- "end";
+    "A" -- "start";
+    "b" -- "d";
+    "b" -- "end";
+    "b" -- "start";
+}
+"#
+        );
+
+        let usage = tunnel_usage(&edges, &Policy::part_1());
+        assert_eq!(usage[&tunnel("start", "A")], 7);
+        assert_eq!(usage[&tunnel("b", "start")], 3);
+        assert_eq!(usage.get(&tunnel("b", "d")), None);
+        // Every path ends through exactly one tunnel into end
+        assert_eq!(
+            usage[&tunnel("A", "end")] + usage[&tunnel("b", "end")],
+            part_1(&edges)
+        );
+
+        let dot = to_dot(&edges, Some(&usage));
+        assert!(dot.contains(r#""A" -- "start" [label=7, penwidth="#));
+        assert!(dot.contains(r#""b" -- "d" [label=0, penwidth=1.0, style=dashed];"#));
+    }
+
     #[test]
     fn part_1_examples() {
         assert_eq!(part_1(&parse_input(SMALL_INPUT)), 10);
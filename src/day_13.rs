@@ -1,9 +1,11 @@
-use itertools::Either::{Left, Right};
-use itertools::Itertools;
+use itertools::{iproduct, Itertools};
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashSet;
-use std::fs::read_to_string;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::ops::RangeInclusive;
 
 use crate::utils::SolverResult;
 
@@ -44,31 +46,171 @@ fn part_1(dots: &Dots, folds: &[Fold]) -> usize {
     fold(dots, folds.first().unwrap()).len()
 }
 
-fn part_2(dots: &Dots, folds: &[Fold]) -> String {
-    let dots = folds.iter().fold(dots.clone(), |d, f| fold(&d, f));
+const LETTER_WIDTH: usize = 4;
+const LETTER_HEIGHT: usize = 6;
+// Letters are separated by a blank column.
+const LETTER_SPACING: usize = LETTER_WIDTH + 1;
 
-    let (x_folds, y_folds): (Vec<usize>, Vec<usize>) =
-        folds.iter().partition_map(|f| match f.direction {
-            FoldDirection::X => Left(f.coordinate),
-            FoldDirection::Y => Right(f.coordinate),
-        });
+// The block letters Advent of Code draws with, side by side.
+const ALPHABET: &str = "ABCEFGHIJKLOPRSUZ";
+const FONT: [&str; LETTER_HEIGHT] = [
+    ".##..###...##..####.####..##..#..#..###...##.#..#.#.....##..###..###...###.#..#.####.",
+    "#..#.#..#.#..#.#....#....#..#.#..#...#.....#.#.#..#....#..#.#..#.#..#.#....#..#....#.",
+    "#..#.###..#....###..###..#....####...#.....#.##...#....#..#.#..#.#..#.#....#..#...#..",
+    "####.#..#.#....#....#....#.##.#..#...#.....#.#.#..#....#..#.###..###...##..#..#..#...",
+    "#..#.#..#.#..#.#....#....#..#.#..#...#..#..#.#.#..#....#..#.#....#.#.....#.#..#.#....",
+    "#..#.###...##..####.#.....###.#..#..###..##..#..#.####..##..#....#..#.###...##..####.",
+];
 
-    let x_max = *x_folds.iter().min().unwrap();
-    let y_max = *y_folds.iter().min().unwrap();
+// The letter whose left edge is at column left, as a bitmask of its dots read row by row.
+fn glyph(is_dot: impl Fn(usize, usize) -> bool, left: usize) -> u32 {
+    iproduct!(0..LETTER_HEIGHT, 0..LETTER_WIDTH)
+        .fold(0, |glyph, (y, x)| glyph << 1 | is_dot(left + x, y) as u32)
+}
 
-    let mut letters: String = (0..y_max)
-        .map(|y| {
-            let mut line: String = (0..x_max)
-                .map(|x| if dots.contains(&(x, y)) { '█' } else { ' ' })
-                .collect();
-            line.push('\n');
-            line
+lazy_static! {
+    static ref LETTERS: HashMap<u32, char> = ALPHABET
+        .chars()
+        .enumerate()
+        .map(|(i, letter)| {
+            let is_dot = |x: usize, y: usize| FONT[y].as_bytes()[x] == b'#';
+            (glyph(is_dot, i * LETTER_SPACING), letter)
         })
         .collect();
+}
+
+#[derive(Debug, PartialEq)]
+enum OcrError {
+    NoDots,
+    WrongHeight(usize),
+    // The letters that could be read, with a ? for each of the unknown ones.
+    UnknownGlyphs(String),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::NoDots => write!(f, "there are no dots to read"),
+            OcrError::WrongHeight(height) => write!(
+                f,
+                "the dots are {} rows tall, but letters are {}",
+                height, LETTER_HEIGHT
+            ),
+            OcrError::UnknownGlyphs(letters) => write!(
+                f,
+                "unknown letters at positions {} (read {})",
+                letters.chars().positions(|c| c == '?').join(", "),
+                letters
+            ),
+        }
+    }
+}
+
+impl Error for OcrError {}
+
+fn read_letters(dots: &Dots) -> Result<String, OcrError> {
+    let width = dots
+        .iter()
+        .map(|&(x, _)| x + 1)
+        .max()
+        .ok_or(OcrError::NoDots)?;
+    let height = dots.iter().map(|&(_, y)| y + 1).max().unwrap();
+
+    if height != LETTER_HEIGHT {
+        return Err(OcrError::WrongHeight(height));
+    }
+
+    let letters: String = (0..width.div_ceil(LETTER_SPACING))
+        .map(|i| {
+            let glyph = glyph(|x, y| dots.contains(&(x, y)), i * LETTER_SPACING);
+            *LETTERS.get(&glyph).unwrap_or(&'?')
+        })
+        .collect();
+
+    if letters.contains('?') {
+        Err(OcrError::UnknownGlyphs(letters))
+    } else {
+        Ok(letters)
+    }
+}
+
+// The columns and rows that the dots actually cover.
+fn bounds(dots: &Dots) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+    let (left, right) = dots.iter().map(|&(x, _)| x).minmax().into_option()?;
+    let (top, bottom) = dots.iter().map(|&(_, y)| y).minmax().into_option()?;
+
+    Some((left..=right, top..=bottom))
+}
+
+fn render(dots: &Dots) -> String {
+    bounds(dots).map_or_else(String::new, |(xs, ys)| {
+        ys.map(|y| {
+            xs.clone()
+                .map(|x| if dots.contains(&(x, y)) { '█' } else { ' ' })
+                .collect::<String>()
+        })
+        .join("\n")
+    })
+}
+
+// An SVG with a black square for each dot, 10 pixels to a side.
+fn to_svg(dots: &Dots) -> String {
+    let (xs, ys) = bounds(dots).unwrap_or((0..=0, 0..=0));
+    let (width, height) = (xs.end() - xs.start() + 1, ys.end() - ys.start() + 1);
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n{}</svg>\n",
+        xs.start(),
+        ys.start(),
+        width,
+        height,
+        width * 10,
+        height * 10,
+        dots.iter()
+            .sorted_by_key(|&&(x, y)| (y, x))
+            .map(|(x, y)| format!("  <rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\"/>\n", x, y))
+            .join("")
+    )
+}
+
+// A plain (ASCII) PBM image with a black pixel for each dot. Lines in the format
+// mustn't be longer than 70 characters, so long rows are wrapped after 35 pixels.
+fn to_pbm(dots: &Dots) -> String {
+    let (xs, ys) = bounds(dots).unwrap_or((0..=0, 0..=0));
+
+    format!(
+        "P1\n{} {}\n{}\n",
+        xs.end() - xs.start() + 1,
+        ys.end() - ys.start() + 1,
+        ys.map(|y| {
+            xs.clone()
+                .map(|x| if dots.contains(&(x, y)) { '1' } else { '0' })
+                .chunks(35)
+                .into_iter()
+                .map(|mut pixels| pixels.join(" "))
+                .join("\n")
+        })
+        .join("\n")
+    )
+}
 
-    letters.insert(0, '\n');
+// The sheet after each fold in turn.
+fn sheets(dots: &Dots, folds: &[Fold]) -> Vec<Dots> {
+    folds
+        .iter()
+        .scan(dots.clone(), |dots, f| {
+            *dots = fold(dots, f);
+            Some(dots.clone())
+        })
+        .collect()
+}
 
-    letters
+// The letters spelled out by the folded dots, or (if they can't be read) why not and
+// the dots themselves.
+fn part_2(dots: &Dots, folds: &[Fold]) -> String {
+    let dots = folds.iter().fold(dots.clone(), |d, f| fold(&d, f));
+
+    read_letters(&dots).unwrap_or_else(|e| format!("{}\n{}", e, render(&dots)))
 }
 
 lazy_static! {
@@ -108,11 +250,37 @@ fn parse_input(input: &str) -> (Dots, Vec<Fold>) {
 }
 
 pub fn solve() -> SolverResult {
+    solve_with(false, false)
+}
+
+// Optionally also writes the final sheet as SVG and PBM images, and with every_fold
+// the sheet after every fold too.
+pub fn solve_with(images: bool, every_fold: bool) -> SolverResult {
     let (dots, folds) = parse_input(&read_to_string("data/day_13.txt")?);
 
     println!("Part 1: {}", part_1(&dots, &folds));
     println!("Part 2: {}", part_2(&dots, &folds));
 
+    if !images && !every_fold {
+        return Ok(());
+    }
+
+    let sheets = sheets(&dots, &folds);
+    let write_sheet = |name: &str, sheet: &Dots| -> SolverResult {
+        write(format!("data/{}.svg", name), to_svg(sheet))?;
+        write(format!("data/{}.pbm", name), to_pbm(sheet))?;
+        Ok(())
+    };
+
+    if every_fold {
+        sheets
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, sheet)| write_sheet(&format!("day_13_fold_{}", i + 1), sheet))?;
+    }
+    write_sheet("day_13", sheets.last().unwrap_or(&dots))?;
+    println!("Wrote the folded sheet to data/day_13.svg and data/day_13.pbm");
+
     Ok(())
 }
 
@@ -143,9 +311,117 @@ mod tests {
 fold along y=7
 fold along x=5";
 
+    // Writes out text in the block letters, the way a folded sheet would show it.
+    fn write(text: &str) -> Dots {
+        text.chars()
+            .enumerate()
+            .flat_map(|(i, letter)| {
+                let offset = ALPHABET.find(letter).unwrap() * LETTER_SPACING;
+
+                iproduct!(0..LETTER_WIDTH, 0..LETTER_HEIGHT)
+                    .filter(move |&(x, y)| FONT[y].as_bytes()[offset + x] == b'#')
+                    .map(move |(x, y)| (i * LETTER_SPACING + x, y))
+            })
+            .collect()
+    }
+
     #[test]
     fn part_1_examples() {
         let (dots, folds) = parse_input(INPUT);
         assert_eq!(part_1(&dots, &folds), 17);
     }
+
+    #[test]
+    fn part_2_examples() {
+        let (dots, folds) = parse_input(INPUT);
+
+        // The example folds into a square, which isn't a letter
+        assert_eq!(
+            part_2(&dots, &folds),
+            "\
+the dots are 5 rows tall, but letters are 6
+█████
+█   █
+█   █
+█   █
+█████"
+        );
+    }
+
+    #[test]
+    fn render_sheets() {
+        let (dots, folds) = parse_input(INPUT);
+        let folded = sheets(&dots, &folds);
+        assert_eq!(folded.len(), 2);
+
+        assert_eq!(
+            render(&folded[0]),
+            "\
+█ ██  █  █ 
+█   █      
+      █   █
+█   █      
+ █ █  █ ███"
+        );
+        assert_eq!(
+            to_pbm(&folded[1]),
+            "\
+P1
+5 5
+1 1 1 1 1
+1 0 0 0 1
+1 0 0 0 1
+1 0 0 0 1
+1 1 1 1 1
+"
+        );
+
+        let svg = to_svg(&folded[1]);
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 5 5" width="50" height="50">"#
+        ));
+        assert_eq!(svg.matches("<rect").count(), 16);
+        assert!(svg.contains(r#"<rect x="4" y="4" width="1" height="1"/>"#));
+
+        // Folding only one way still works, and the bounds follow the dots
+        let dots = Dots::from([(1, 2), (9, 5)]);
+        let folds = [Fold {
+            direction: FoldDirection::X,
+            coordinate: 6,
+        }];
+        let folded = sheets(&dots, &folds);
+        assert_eq!(render(&folded[0]), "█  \n   \n   \n  █");
+        assert_eq!(to_pbm(&Dots::new()), "P1\n1 1\n0\n");
+
+        // Rows wider than 35 pixels are wrapped
+        let dots = Dots::from([(0, 0), (35, 0), (79, 1)]);
+        let pbm = to_pbm(&dots);
+        assert_eq!(
+            pbm.lines().map(|line| line.len()).collect_vec(),
+            vec![2, 4, 69, 69, 19, 69, 69, 19]
+        );
+        assert!(pbm.lines().nth(2).unwrap().starts_with("1 0"));
+        assert!(pbm.lines().nth(3).unwrap().starts_with("1 0"));
+        assert!(pbm.ends_with("0 1\n"));
+        assert_eq!(pbm.lines().skip(2).join("").matches('1').count(), 3);
+    }
+
+    #[test]
+    fn read_all_letters() {
+        assert_eq!(read_letters(&write(ALPHABET)), Ok(ALPHABET.to_owned()));
+        assert_eq!(read_letters(&write("LIZ")), Ok("LIZ".to_owned()));
+        assert_eq!(read_letters(&Dots::new()), Err(OcrError::NoDots));
+
+        // Smudge the second letter
+        let mut dots = write("EFL");
+        dots.insert((LETTER_SPACING + 3, 5));
+        assert_eq!(
+            read_letters(&dots),
+            Err(OcrError::UnknownGlyphs("E?L".to_owned()))
+        );
+        assert_eq!(
+            OcrError::UnknownGlyphs("E?L".to_owned()).to_string(),
+            "unknown letters at positions 1 (read E?L)"
+        );
+    }
 }
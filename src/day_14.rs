@@ -1,56 +1,255 @@
+use crate::utils::matrix::Matrix;
 use crate::utils::SolverResult;
+use itertools::Either::{self, Left, Right};
 use itertools::Itertools;
 use itertools::MinMaxResult::MinMax;
+use num::{One, Zero};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs::read_to_string;
+use std::mem;
+use std::ops::Mul;
 
 type Polymer = Vec<char>;
 type Rules = HashMap<[char; 2], char>;
 
-fn grow(polymer: &Polymer, rules: &Rules, steps: usize) -> usize {
+// The number of each element in a polymer.
+type Histogram<T> = HashMap<char, T>;
+
+// Every pair that can appear while growing the polymer, in order.
+fn pairs(polymer: &Polymer, rules: &Rules) -> Vec<[char; 2]> {
+    polymer
+        .array_windows()
+        .cloned()
+        .chain(
+            rules.iter().flat_map(|(&[left, right], &insert)| {
+                [[left, right], [left, insert], [insert, right]]
+            }),
+        )
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+fn of<T: Zero + One>(n: usize) -> T {
+    (0..n).fold(T::zero(), |total, _| total + T::one())
+}
+
+// The number of each element in the polymer after growing it for the given number of steps.
+// Generic so that very long polymers can be counted with a BigUint (or modulo something, with Wrapping).
+fn histogram<T>(polymer: &Polymer, rules: &Rules, steps: u64) -> Histogram<T>
+where
+    T: Clone + Zero + One,
+    for<'a> &'a T: Mul<Output = T>,
+{
     // Count the number of each kind of pair in the polymer as we grow it.
     // Because each pair is created "inside" an existing pair,
     // we create two new pairs which cannot interact with any other pairs being created.
     // The process is therefore "local", and we don't have to worry about the overall
     // ordering of the pairs long the polymer.
-    let pair_counts = (0..steps).fold(
-        polymer.as_slice().array_windows().cloned().counts(),
-        |counts, _step| {
-            let mut new_counts = HashMap::new();
-            counts.iter().for_each(|(pair, count)| {
-                if let Some(&insert) = rules.get(pair) {
-                    let left = new_counts.entry([pair[0], insert]).or_insert(0);
-                    *left += count;
-
-                    let right = new_counts.entry([insert, pair[1]]).or_insert(0);
-                    *right += count;
-                } else {
-                    let orig = new_counts.entry(*pair).or_insert(0);
-                    *orig += count;
-                }
-            });
-            new_counts
-        },
+    // That makes each step a linear map on the pair counts, so we can take all the steps
+    // at once by raising its matrix to the number of steps.
+    let pairs = pairs(polymer, rules);
+    let transition = Matrix::<T>::from_fn(pairs.len(), |to, from| {
+        let pair = pairs[from];
+        let produced = match rules.get(&pair) {
+            Some(&insert) => vec![[pair[0], insert], [insert, pair[1]]],
+            None => vec![pair],
+        };
+        of(produced.iter().filter(|&&p| p == pairs[to]).count())
+    });
+
+    let initial = polymer.as_slice().array_windows().counts();
+    let pair_counts = transition.pow_apply(
+        steps,
+        &pairs
+            .iter()
+            .map(|pair| of(initial.get(pair).copied().unwrap_or(0)))
+            .collect_vec(),
     );
 
     // Now count the left character in each pair.
     // The right character is the left character of some other pair, so it will be counted then...
-    let mut char_counts = HashMap::new();
-    pair_counts.iter().for_each(|(pair, count)| {
-        let left = char_counts.entry(pair[0]).or_insert(0);
-        *left += count;
+    let mut char_counts = Histogram::new();
+    pairs.iter().zip(pair_counts).for_each(|(pair, count)| {
+        let left = char_counts.entry(pair[0]).or_insert_with(T::zero);
+        *left = mem::replace(left, T::zero()) + count;
     });
     // ... except for the very last character, which is not the left character in any pair.
-    let last = char_counts.entry(*polymer.last().unwrap()).or_insert(0);
-    *last += 1;
+    let last = char_counts
+        .entry(*polymer.last().unwrap())
+        .or_insert_with(T::zero);
+    *last = mem::replace(last, T::zero()) + T::one();
+
+    // Elements that only appear in the rules might never actually be inserted.
+    char_counts.retain(|_, count| !count.is_zero());
 
-    if let MinMax(min, max) = char_counts.values().minmax() {
+    char_counts
+}
+
+fn grow(polymer: &Polymer, rules: &Rules, steps: u64) -> usize {
+    if let MinMax(min, max) = histogram::<usize>(polymer, rules, steps).values().minmax() {
         max - min
     } else {
         0
     }
 }
 
+// The polymer itself after growing it for the given number of steps.
+// It doubles in length every step, so this is only practical for small numbers of steps.
+fn expand(polymer: &Polymer, rules: &Rules, steps: usize) -> Polymer {
+    (0..steps).fold(polymer.clone(), |polymer, _step| {
+        polymer
+            .iter()
+            .take(1)
+            .cloned()
+            .chain(polymer.array_windows().flat_map(|pair| {
+                rules
+                    .get(pair)
+                    .into_iter()
+                    .cloned()
+                    .chain(std::iter::once(pair[1]))
+            }))
+            .collect()
+    })
+}
+
+// Parts of the grown polymer, found without building the whole thing.
+struct Expansion<'a> {
+    polymer: &'a Polymer,
+    rules: &'a Rules,
+    steps: usize,
+    index: HashMap<[char; 2], usize>,
+    // inserted[s][i] is how many elements are inserted between the two elements of the
+    // i-th pair in s steps, saturating at usize::MAX. Once every count has saturated the
+    // rows stop changing, so the table stops there and later steps use its last row
+    // (typically after about 64 steps, though rules that only grow polynomially never
+    // saturate and keep a row for every step).
+    inserted: Vec<Vec<usize>>,
+}
+
+impl<'a> Expansion<'a> {
+    fn new(polymer: &'a Polymer, rules: &'a Rules, steps: usize) -> Self {
+        let pairs = pairs(polymer, rules);
+        let index: HashMap<[char; 2], usize> = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, &pair)| (pair, i))
+            .collect();
+
+        // A pair with a rule grows into its left pair, the inserted element, and its right pair.
+        let mut inserted = vec![vec![0usize; pairs.len()]];
+        while inserted.len() <= steps {
+            let previous = inserted.last().unwrap();
+            let next = pairs
+                .iter()
+                .map(|&[left, right]| match rules.get(&[left, right]) {
+                    Some(&insert) => previous[index[&[left, insert]]]
+                        .saturating_add(1)
+                        .saturating_add(previous[index[&[insert, right]]]),
+                    None => 0,
+                })
+                .collect_vec();
+
+            if &next == previous {
+                break;
+            }
+            inserted.push(next);
+        }
+
+        Expansion {
+            polymer,
+            rules,
+            steps,
+            index,
+            inserted,
+        }
+    }
+
+    fn inserted(&self, pair: [char; 2], steps: usize) -> usize {
+        self.inserted[steps.min(self.inserted.len() - 1)][self.index[&pair]]
+    }
+
+    // The length of the grown polymer (or usize::MAX, if it's at least that long).
+    fn len(&self) -> usize {
+        self.polymer
+            .array_windows()
+            .fold(self.polymer.len(), |len, &pair| {
+                len.saturating_add(self.inserted(pair, self.steps))
+            })
+    }
+
+    // The k-th element (from 0) of the grown polymer.
+    fn get(&self, mut k: usize) -> Option<char> {
+        for &pair in self.polymer.array_windows() {
+            if k == 0 {
+                return Some(pair[0]);
+            }
+            k -= 1;
+
+            let inserted = self.inserted(pair, self.steps);
+            if k < inserted {
+                return Some(self.descend(pair, k));
+            }
+            k -= inserted;
+        }
+
+        self.polymer.last().filter(|_| k == 0).copied()
+    }
+
+    // The k-th of the elements inserted inside the pair, walking down through the steps
+    // into whichever half contains it.
+    fn descend(&self, mut pair: [char; 2], mut k: usize) -> char {
+        (1..=self.steps)
+            .rev()
+            .find_map(|steps| {
+                let insert = self.rules[&pair];
+                let left = self.inserted([pair[0], insert], steps - 1);
+
+                match k.cmp(&left) {
+                    Ordering::Less => pair = [pair[0], insert],
+                    Ordering::Equal => return Some(insert),
+                    Ordering::Greater => {
+                        k -= left + 1;
+                        pair = [insert, pair[1]];
+                    }
+                }
+                None
+            })
+            .expect("the pair's expansion contains k")
+    }
+
+    // The first n elements of the grown polymer (or all of it, if it's shorter than that).
+    fn prefix(&self, n: usize) -> Polymer {
+        // Walk the tree of expansions in order, with the pairs still to expand on a stack.
+        let mut stack: Vec<Either<char, ([char; 2], usize)>> = vec![];
+        if let Some(&last) = self.polymer.last() {
+            stack.push(Left(last));
+        }
+        self.polymer.array_windows().rev().for_each(|&pair| {
+            stack.push(Right((pair, self.steps)));
+            stack.push(Left(pair[0]));
+        });
+
+        let mut prefix = Polymer::new();
+        while prefix.len() < n {
+            match stack.pop() {
+                None => break,
+                Some(Left(element)) => prefix.push(element),
+                Some(Right((pair, steps))) => {
+                    if let (Some(&insert), true) = (self.rules.get(&pair), steps > 0) {
+                        stack.push(Right(([insert, pair[1]], steps - 1)));
+                        stack.push(Left(insert));
+                        stack.push(Right(([pair[0], insert], steps - 1)));
+                    }
+                }
+            }
+        }
+
+        prefix
+    }
+}
+
 fn parse_input(input: &str) -> (Polymer, Rules) {
     let mut lines = input.lines();
     let polymer: Polymer = lines.next().unwrap().chars().collect();
@@ -80,9 +279,65 @@ pub fn solve() -> SolverResult {
     Ok(())
 }
 
+// The longest polymer that solve_with will build in full.
+const MAX_SHOWN: usize = 1 << 20;
+
+// Also describes the polymer after the given number of steps: its length, and optionally
+// the whole thing, its k-th element (from 0) and its first n elements.
+pub fn solve_with(
+    steps: usize,
+    whole: bool,
+    element: Option<usize>,
+    prefix: Option<usize>,
+) -> SolverResult {
+    let (polymer, rules) = parse_input(&read_to_string("data/day_14.txt")?);
+
+    println!("Part 1: {}", grow(&polymer, &rules, 10));
+    println!("Part 2: {}", grow(&polymer, &rules, 40));
+
+    let expansion = Expansion::new(&polymer, &rules, steps);
+    match expansion.len() {
+        usize::MAX => println!("Length after {} steps: at least {}", steps, usize::MAX),
+        len => println!("Length after {} steps: {}", steps, len),
+    }
+
+    if whole {
+        if expansion.len() > MAX_SHOWN {
+            return Err(format!(
+                "the polymer is too long to show in full after {} steps; ask for a --prefix instead",
+                steps
+            )
+            .into());
+        }
+        println!(
+            "Polymer: {}",
+            expand(&polymer, &rules, steps).iter().collect::<String>()
+        );
+    }
+
+    if let Some(k) = element {
+        match expansion.get(k) {
+            Some(element) => println!("Element {}: {}", k, element),
+            None => return Err(format!("the polymer has no element {}", k).into()),
+        }
+    }
+
+    if let Some(n) = prefix {
+        println!(
+            "First {} elements: {}",
+            n,
+            expansion.prefix(n).iter().collect::<String>()
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num::BigUint;
+    use std::num::Wrapping;
 
     const INPUT: &str = "\
 NNCB
@@ -115,4 +370,172 @@ CN -> C";
         let (polymer, rules) = parse_input(INPUT);
         assert_eq!(grow(&polymer, &rules, 40), 2188189693529);
     }
+
+    // The original step-by-step growth, to check the matrix version against.
+    fn histogram_by_steps(polymer: &Polymer, rules: &Rules, steps: usize) -> Histogram<usize> {
+        // Count the number of each kind of pair in the polymer as we grow it.
+        // Because each pair is created "inside" an existing pair,
+        // we create two new pairs which cannot interact with any other pairs being created.
+        // The process is therefore "local", and we don't have to worry about the overall
+        // ordering of the pairs long the polymer.
+        let pair_counts = (0..steps).fold(
+            polymer.as_slice().array_windows().cloned().counts(),
+            |counts, _step| {
+                let mut new_counts = HashMap::new();
+                counts.iter().for_each(|(pair, count)| {
+                    if let Some(&insert) = rules.get(pair) {
+                        let left = new_counts.entry([pair[0], insert]).or_insert(0);
+                        *left += count;
+
+                        let right = new_counts.entry([insert, pair[1]]).or_insert(0);
+                        *right += count;
+                    } else {
+                        let orig = new_counts.entry(*pair).or_insert(0);
+                        *orig += count;
+                    }
+                });
+                new_counts
+            },
+        );
+
+        // Now count the left character in each pair.
+        // The right character is the left character of some other pair, so it will be counted then...
+        let mut char_counts = HashMap::new();
+        pair_counts.iter().for_each(|(pair, count)| {
+            let left = char_counts.entry(pair[0]).or_insert(0);
+            *left += count;
+        });
+        // ... except for the very last character, which is not the left character in any pair.
+        let last = char_counts.entry(*polymer.last().unwrap()).or_insert(0);
+        *last += 1;
+
+        char_counts
+    }
+
+    #[test]
+    fn matches_reference() {
+        let (polymer, rules) = parse_input(INPUT);
+
+        [0, 1, 10, 40].iter().for_each(|&steps| {
+            assert_eq!(
+                histogram::<usize>(&polymer, &rules, steps as u64),
+                histogram_by_steps(&polymer, &rules, steps)
+            );
+        });
+        assert_eq!(
+            histogram::<usize>(&polymer, &rules, 10),
+            Histogram::from([('B', 1749), ('C', 298), ('H', 161), ('N', 865)])
+        );
+    }
+
+    #[test]
+    fn huge_polymers() {
+        let (polymer, rules) = parse_input(INPUT);
+
+        // The polymer doubles in length (less one) every step
+        let counts = histogram::<BigUint>(&polymer, &rules, 10_000);
+        assert_eq!(
+            counts.values().sum::<BigUint>(),
+            BigUint::from(3u8) * BigUint::from(2u8).pow(10_000) + 1u8
+        );
+
+        // Counting modulo 2^64 agrees with the exact counts
+        let wrapped = histogram::<Wrapping<u64>>(&polymer, &rules, 10_000);
+        counts.iter().for_each(|(c, count)| {
+            assert_eq!(
+                wrapped[c].0,
+                (count % BigUint::from(2u8).pow(64))
+                    .to_u64_digits()
+                    .first()
+                    .copied()
+                    .unwrap_or(0)
+            )
+        });
+
+        // Exact counts after 10^12 steps would have 10^12 bits, but we can still count them modulo 2^64
+        let wrapped = histogram::<Wrapping<u64>>(&polymer, &rules, 1_000_000_000_000);
+        assert_eq!(wrapped.values().sum::<Wrapping<u64>>(), Wrapping(1));
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_million_steps() {
+        let (polymer, rules) = parse_input(INPUT);
+
+        let start = std::time::Instant::now();
+        let counts = histogram::<BigUint>(&polymer, &rules, 1_000_000);
+        println!("10^6 steps with BigUint: {:?}", start.elapsed());
+
+        assert_eq!(
+            counts.values().sum::<BigUint>(),
+            BigUint::from(3u8) * BigUint::from(2u8).pow(1_000_000) + 1u8
+        );
+    }
+
+    #[test]
+    fn expand_examples() {
+        let (polymer, rules) = parse_input(INPUT);
+
+        [
+            "NNCB",
+            "NCNBCHB",
+            "NBCCNBBBCBHCB",
+            "NBBBCNCCNBBNBNBBCHBHHBCHB",
+            "NBBNBNBBCCNBCNCCNBBNBBNBBBNBBNBBCBHCBHHNHCBBCBHCB",
+        ]
+        .iter()
+        .enumerate()
+        .for_each(|(steps, &expected)| {
+            let expanded = expand(&polymer, &rules, steps);
+            assert_eq!(expanded.iter().collect::<String>(), expected);
+
+            let expansion = Expansion::new(&polymer, &rules, steps);
+            assert_eq!(expansion.len(), expected.len());
+            assert_eq!(expansion.prefix(usize::MAX), expanded);
+            assert_eq!(
+                (0..=expected.len()).map(|k| expansion.get(k)).collect_vec(),
+                expected.chars().map(Some).chain([None]).collect_vec()
+            );
+        });
+
+        assert_eq!(expand(&polymer, &rules, 5).len(), 97);
+        assert_eq!(expand(&polymer, &rules, 10).len(), 3073);
+    }
+
+    #[test]
+    fn prefix_queries() {
+        let (polymer, rules) = parse_input(INPUT);
+
+        let expanded = expand(&polymer, &rules, 15);
+        let expansion = Expansion::new(&polymer, &rules, 15);
+        assert_eq!(expansion.prefix(100), expanded[..100]);
+        assert_eq!(expansion.prefix(0), vec![]);
+        [0, 1, 1000, 12345, expanded.len() - 1]
+            .iter()
+            .for_each(|&k| assert_eq!(expansion.get(k), Some(expanded[k])));
+
+        // After 40 steps the polymer is about 3 * 2^40 elements long, and after 100 it's too
+        // long to even count, but we can still look at parts of it
+        let expansion = Expansion::new(&polymer, &rules, 40);
+        assert_eq!(expansion.len(), 3 * (1 << 40) + 1);
+        assert_eq!(expansion.get(expansion.len() - 1), Some('B'));
+        assert_eq!(expansion.get(expansion.len()), None);
+
+        let expansion = Expansion::new(&polymer, &rules, 100);
+        assert_eq!(expansion.len(), usize::MAX);
+        let prefix = expansion.prefix(50);
+        assert_eq!(prefix.len(), 50);
+        prefix
+            .iter()
+            .enumerate()
+            .for_each(|(k, &c)| assert_eq!(expansion.get(k), Some(c)));
+
+        // The table stops growing once the counts saturate, and later steps reuse its last row
+        let table = expansion.inserted.len();
+        assert!(table < 70);
+        let longer = Expansion::new(&polymer, &rules, 10_000);
+        assert_eq!(longer.inserted.len(), table);
+        assert_eq!(longer.len(), usize::MAX);
+        assert_eq!(longer.prefix(50).len(), 50);
+    }
 }
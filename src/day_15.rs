@@ -1,138 +1,152 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
-use std::fs::read_to_string;
+use std::collections::HashSet;
+use std::fs::{read_to_string, write};
 
-use itertools::iproduct;
+use itertools::{iproduct, Itertools};
 
+use crate::utils::graph::{dijkstra_bounded, Graph, IndexedGraph, Path};
+use crate::utils::grid::{Grid, Position, ORTHOGONAL};
 use crate::utils::SolverResult;
 
-type Position = (isize, isize);
-type Nodes = HashMap<Position, usize>;
-type Edges = HashMap<Position, Vec<Position>>;
-type Weights = HashMap<(Position, Position), usize>;
-
-fn neighbours(p: &Position) -> Vec<Position> {
-    let (x, y) = p;
-    let x_m = x - 1;
-    let x_p = x + 1;
-    let y_m = y - 1;
-    let y_p = y + 1;
-
-    vec![(x_m, *y), (*x, y_m), (*x, y_p), (x_p, *y)]
+// The risk levels of the original map, which are tiled on the fly (each tile
+// further right or down having its risk increased by 1, wrapping 9 back to 1)
+// rather than building the full cavern.
+struct Cavern {
+    risk: Grid<u8>,
+    tiles: usize,
 }
 
-// https://doc.rust-lang.org/std/collections/binary_heap/index.html
-#[derive(Copy, Clone, Eq, PartialEq)]
-struct State {
-    position: Position,
-    cost: usize,
-}
+impl Cavern {
+    fn tiled(&self, tiles: usize) -> Self {
+        Cavern {
+            risk: self.risk.clone(),
+            tiles,
+        }
+    }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    fn width(&self) -> usize {
+        self.risk.width() * self.tiles
     }
-}
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other
-            .cost
-            .cmp(&self.cost)
-            .then_with(|| self.position.cmp(&other.position))
+    fn height(&self) -> usize {
+        self.risk.height() * self.tiles
+    }
+
+    fn risk(&self, x: usize, y: usize) -> usize {
+        let (cols, rows) = (self.risk.width(), self.risk.height());
+        let risk = self.risk[(x % cols, y % rows)] as usize + x / cols + y / rows;
+
+        (risk - 1) % 9 + 1
     }
 }
 
-fn find_shortest_path(edges: &Edges, weights: &Weights) -> Option<usize> {
-    let mut frontier = BinaryHeap::new();
-    let mut shortest_paths = HashMap::new();
+// Moving onto a position costs its risk level.
+impl Graph for Cavern {
+    type Node = Position;
 
-    let top_left = *edges.keys().min().unwrap();
-    let bottom_right = *edges.keys().max().unwrap();
+    fn neighbours(&self, &(x, y): &Position) -> Vec<(Position, usize)> {
+        ORTHOGONAL
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let n = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+                (n.0 < self.width() && n.1 < self.height()).then(|| (n, self.risk(n.0, n.1)))
+            })
+            .collect()
+    }
+}
 
-    frontier.push(State {
-        position: top_left,
-        cost: 0,
-    });
-    shortest_paths.insert(top_left, 0);
+impl IndexedGraph for Cavern {
+    fn size(&self) -> usize {
+        self.width() * self.height()
+    }
 
-    while let Some(current) = frontier.pop() {
-        if current.position == bottom_right {
-            return Some(current.cost);
-        }
-        if let Some(neighbours) = edges.get(&current.position) {
-            neighbours.iter().for_each(|&n| {
-                let next = State {
-                    position: n,
-                    cost: current.cost + *weights.get(&(current.position, n)).unwrap(),
-                };
-
-                if next.cost < *shortest_paths.get(&n).unwrap_or(&usize::MAX) {
-                    frontier.push(next);
-                    shortest_paths.insert(n, next.cost);
-                }
-            });
-        }
+    fn index(&self, &(x, y): &Position) -> usize {
+        y * self.width() + x
     }
+}
+
+// Every risk level is between 1 and 9, so the search can use a bucket queue.
+fn lowest_risk_path(cavern: &Cavern) -> Option<Path<Position>> {
+    let goal = (cavern.width() - 1, cavern.height() - 1);
 
-    None
+    dijkstra_bounded(cavern, [(0, 0)], |&p| p == goal, 9)
 }
 
-fn parse_nodes(input: &str) -> Nodes {
-    input
-        .lines()
-        .enumerate()
-        .flat_map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(move |(x, w)| ((x as isize, y as isize), w.to_digit(10).unwrap() as usize))
+// The risk levels with the path picked out in bold yellow, for a terminal.
+fn render_ansi(cavern: &Cavern, path: &Path<Position>) -> String {
+    let on_path: HashSet<&Position> = path.nodes().collect();
+
+    (0..cavern.height())
+        .map(|y| {
+            (0..cavern.width())
+                .map(|x| {
+                    if on_path.contains(&(x, y)) {
+                        format!("\x1b[1;33m{}\x1b[0m", cavern.risk(x, y))
+                    } else {
+                        format!("\x1b[2m{}\x1b[0m", cavern.risk(x, y))
+                    }
+                })
+                .join("")
         })
-        .collect()
+        .join("\n")
 }
 
-fn edges_and_weights(nodes: &Nodes) -> (Edges, Weights) {
-    nodes.iter().fold(
-        (Edges::new(), Weights::new()),
-        |(mut edges, mut weights), (&p, _)| {
-            neighbours(&p).iter().for_each(|&n| {
-                if let Some(&w) = nodes.get(&n) {
-                    weights.insert((p, n), w);
-                    edges.entry(p).or_insert_with(Vec::new).push(n);
-                }
-            });
-
-            (edges, weights)
-        },
-    )
-}
+// A binary PPM image with a pixel per position: riskier positions are darker,
+// and the path is red.
+fn render_ppm(cavern: &Cavern, path: &Path<Position>) -> Vec<u8> {
+    let on_path: HashSet<&Position> = path.nodes().collect();
 
-fn expand(input: &Nodes) -> Nodes {
-    let (mx, my) = input.keys().max().unwrap();
-    let (mx, my) = (mx + 1, my + 1); // widths of the original chunk
+    let mut image = format!("P6\n{} {}\n255\n", cavern.width(), cavern.height()).into_bytes();
 
-    input
-        .iter()
-        .flat_map(|((x, y), w)| {
-            iproduct!(0..=4isize, 0..=4isize).map(move |(xd, yd)| {
-                let mut new_w = w + (xd + yd) as usize;
-                if new_w >= 10 {
-                    new_w -= 9
-                }
+    iproduct!(0..cavern.height(), 0..cavern.width()).for_each(|(y, x)| {
+        if on_path.contains(&(x, y)) {
+            image.extend([255, 0, 0]);
+        } else {
+            let level = (255 - 25 * cavern.risk(x, y)) as u8;
+            image.extend([level, level, level]);
+        }
+    });
 
-                ((x + (xd * mx), y + (yd * my)), new_w)
-            })
-        })
-        .collect()
+    image
+}
+
+fn parse_cavern(input: &str) -> Cavern {
+    Cavern {
+        risk: Grid::parse_digits(input),
+        tiles: 1,
+    }
 }
 
 pub fn solve() -> SolverResult {
-    let nodes = parse_nodes(&read_to_string("data/day_15.txt")?);
+    solve_with(false)
+}
 
-    let (edges, weights) = edges_and_weights(&nodes);
-    println!("Part 1: {}", find_shortest_path(&edges, &weights).unwrap());
+// Optionally also shows the path through the original cavern in the terminal, and writes
+// both paths to data/day_15_*path.{txt,ppm}.
+pub fn solve_with(render: bool) -> SolverResult {
+    let cavern = parse_cavern(&read_to_string("data/day_15.txt")?);
 
-    let (edges, weights) = edges_and_weights(&expand(&nodes));
-    println!("Part 2: {}", find_shortest_path(&edges, &weights).unwrap());
+    let path = lowest_risk_path(&cavern).unwrap();
+    if render {
+        println!("{}\n", render_ansi(&cavern, &path));
+        write("data/day_15_path.txt", render_ansi(&cavern, &path))?;
+        write("data/day_15_path.ppm", render_ppm(&cavern, &path))?;
+    }
+    println!("Part 1: {}", path.cost);
+
+    let expanded = cavern.tiled(5);
+    let path = lowest_risk_path(&expanded).unwrap();
+    println!("Part 2: {}", path.cost);
+    if render {
+        write(
+            "data/day_15_expanded_path.txt",
+            render_ansi(&expanded, &path),
+        )?;
+        write(
+            "data/day_15_expanded_path.ppm",
+            render_ppm(&expanded, &path),
+        )?;
+        println!("Wrote the rendered paths to data/day_15_*path.{{txt,ppm}}");
+    }
 
     Ok(())
 }
@@ -140,6 +154,81 @@ pub fn solve() -> SolverResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    use crate::utils::graph::a_star;
+    use crate::utils::random::xorshift;
+
+    // The original implementation over HashMaps, kept as a reference for the dense one.
+    type Position = (isize, isize);
+    type Nodes = HashMap<Position, usize>;
+
+    fn neighbours(p: &Position) -> Vec<Position> {
+        let (x, y) = p;
+        let x_m = x - 1;
+        let x_p = x + 1;
+        let y_m = y - 1;
+        let y_p = y + 1;
+
+        vec![(x_m, *y), (*x, y_m), (*x, y_p), (x_p, *y)]
+    }
+
+    impl Graph for Nodes {
+        type Node = Position;
+
+        // Entering a position costs its risk level.
+        fn neighbours(&self, p: &Position) -> Vec<(Position, usize)> {
+            neighbours(p)
+                .into_iter()
+                .filter_map(|n| self.get(&n).map(|&w| (n, w)))
+                .collect()
+        }
+    }
+
+    fn find_shortest_path(nodes: &Nodes) -> Option<Path<Position>> {
+        let top_left = *nodes.keys().min().unwrap();
+        let bottom_right = *nodes.keys().max().unwrap();
+
+        // Every step costs at least 1, so the Manhattan distance never overestimates.
+        a_star(
+            nodes,
+            [top_left],
+            |&p| p == bottom_right,
+            |&(x, y)| (bottom_right.0 - x + bottom_right.1 - y) as usize,
+        )
+    }
+
+    fn parse_nodes(input: &str) -> Nodes {
+        input
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(move |(x, w)| ((x as isize, y as isize), w.to_digit(10).unwrap() as usize))
+            })
+            .collect()
+    }
+
+    fn expand(input: &Nodes) -> Nodes {
+        let (mx, my) = input.keys().max().unwrap();
+        let (mx, my) = (mx + 1, my + 1); // widths of the original chunk
+
+        input
+            .iter()
+            .flat_map(|((x, y), w)| {
+                iproduct!(0..=4isize, 0..=4isize).map(move |(xd, yd)| {
+                    let mut new_w = w + (xd + yd) as usize;
+                    if new_w >= 10 {
+                        new_w -= 9
+                    }
+
+                    ((x + (xd * mx), y + (yd * my)), new_w)
+                })
+            })
+            .collect()
+    }
 
     const INPUT: &str = "\
 1163751742
@@ -153,10 +242,126 @@ mod tests {
 1293138521
 2311944581";
 
+    #[test]
+    fn tiling() {
+        let cavern = parse_cavern("8");
+        let tiled = cavern.tiled(5);
+
+        assert_eq!((tiled.width(), tiled.height()), (5, 5));
+        assert_eq!(
+            (0..5).map(|y| tiled.risk(y, y)).collect::<Vec<_>>(),
+            vec![8, 1, 3, 5, 7]
+        );
+
+        let cavern = parse_cavern(INPUT);
+        let expanded = expand(&parse_nodes(INPUT));
+        let tiled = cavern.tiled(5);
+        assert!(expanded
+            .iter()
+            .all(|(&(x, y), &w)| tiled.risk(x as usize, y as usize) == w));
+    }
+
+    // Deterministic pseudo-random risk levels, for something the size of a real input.
+    fn random_input(size: usize) -> String {
+        let mut random = xorshift(0x2545F4914F6CDD1D);
+
+        (0..size)
+            .map(|_| {
+                random
+                    .by_ref()
+                    .take(size)
+                    .map(|r| char::from(b'1' + (r % 9) as u8))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn matches_reference() {
+        let input = random_input(30);
+        let cavern = parse_cavern(&input);
+        let nodes = parse_nodes(&input);
+
+        assert_eq!(
+            lowest_risk_path(&cavern).map(|p| p.cost),
+            find_shortest_path(&nodes).map(|p| p.cost)
+        );
+        assert_eq!(
+            lowest_risk_path(&cavern.tiled(5)).map(|p| p.cost),
+            find_shortest_path(&expand(&nodes)).map(|p| p.cost)
+        );
+    }
+
+    // Run with cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_dense_grid() {
+        let input = random_input(100);
+
+        let before = Instant::now();
+        let nodes = parse_nodes(&input);
+        let reference = find_shortest_path(&expand(&nodes)).unwrap().cost;
+        let reference_elapsed = before.elapsed();
+
+        let before = Instant::now();
+        let dense = lowest_risk_path(&parse_cavern(&input).tiled(5))
+            .unwrap()
+            .cost;
+        let dense_elapsed = before.elapsed();
+
+        assert_eq!(dense, reference);
+        println!(
+            "Part 2 on 100x100: HashMaps in {:.2?}, dense grid in {:.2?} ({:.1}x faster)",
+            reference_elapsed,
+            dense_elapsed,
+            reference_elapsed.as_secs_f64() / dense_elapsed.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn render_path() {
+        let cavern = parse_cavern("19\n11");
+        let path = lowest_risk_path(&cavern).unwrap();
+        assert_eq!(path.cost, 2);
+
+        assert_eq!(
+            render_ansi(&cavern, &path),
+            "\x1b[1;33m1\x1b[0m\x1b[2m9\x1b[0m\n\x1b[1;33m1\x1b[0m\x1b[1;33m1\x1b[0m"
+        );
+
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend([255, 0, 0, 30, 30, 30, 255, 0, 0, 255, 0, 0]);
+        assert_eq!(render_ppm(&cavern, &path), expected);
+
+        let expanded = cavern.tiled(5);
+        let path = lowest_risk_path(&expanded).unwrap();
+        let image = render_ppm(&expanded, &path);
+        assert!(image.starts_with(b"P6\n10 10\n255\n"));
+        assert_eq!(image.len(), b"P6\n10 10\n255\n".len() + 10 * 10 * 3);
+        assert_eq!(
+            render_ansi(&expanded, &path).matches("\x1b[1;33m").count(),
+            path.steps.len()
+        );
+    }
+
     #[test]
     fn part_1_examples() {
-        let (edges, weights) = edges_and_weights(&parse_nodes(INPUT));
-        assert_eq!(find_shortest_path(&edges, &weights), Some(40));
+        let cavern = parse_cavern(INPUT);
+        let path = lowest_risk_path(&cavern).unwrap();
+        assert_eq!(path.cost, 40);
+        assert_eq!(path.steps.first(), Some(&((0, 0), 0)));
+        assert_eq!(path.steps.last(), Some(&((9, 9), 1)));
+        assert_eq!(path.steps.iter().map(|(_, w)| w).sum::<usize>(), 40);
+        assert!(path
+            .nodes()
+            .tuple_windows()
+            .all(|(a, b)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1));
+
+        let path = find_shortest_path(&parse_nodes(INPUT)).unwrap();
+        assert_eq!(path.cost, 40);
+        assert_eq!(path.steps.len(), 19);
+        assert_eq!(path.steps.iter().map(|(_, w)| w).sum::<usize>(), 40);
     }
 
     #[test]
@@ -165,7 +370,10 @@ mod tests {
         let expanded = &expand(nodes);
         assert_eq!(nodes.len() * 25, expanded.len());
 
-        let (edges, weights) = edges_and_weights(expanded);
-        assert_eq!(find_shortest_path(&edges, &weights), Some(315));
+        assert_eq!(find_shortest_path(expanded).map(|p| p.cost), Some(315));
+
+        let path = lowest_risk_path(&parse_cavern(INPUT).tiled(5)).unwrap();
+        assert_eq!(path.cost, 315);
+        assert_eq!(path.nodes().last(), Some(&(49, 49)));
     }
 }
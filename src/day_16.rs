@@ -1,12 +1,17 @@
 use crate::utils::SolverResult;
-use std::fs::read_to_string;
+use itertools::Itertools;
+use num::{BigUint, CheckedAdd, CheckedMul, Num};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
 
 #[derive(Debug, PartialEq, Eq)]
-enum Packet {
+pub enum Packet {
     Literal {
         version: usize,
         type_id: usize,
-        value: usize,
+        value: BigUint,
     },
     Operator {
         version: usize,
@@ -16,166 +21,725 @@ enum Packet {
     },
 }
 
-fn parse_bin(bin: &str) -> usize {
-    usize::from_str_radix(bin, 2).unwrap()
+#[derive(Debug, PartialEq, Eq)]
+pub enum BitsError {
+    InvalidHex {
+        offset: usize,
+        character: char,
+    },
+    Truncated {
+        offset: usize,
+    },
+    // The sub-packets of a length type 0 packet ran past the length it declared
+    LengthMismatch {
+        offset: usize,
+    },
+    LiteralOverflow {
+        offset: usize,
+    },
+    // A sum or product that is too large for the type it's evaluated as
+    ValueOverflow {
+        offset: usize,
+    },
+    UnknownOperator {
+        offset: usize,
+        type_id: usize,
+    },
+    OperandCount {
+        offset: usize,
+        type_id: usize,
+        count: usize,
+    },
+    Io {
+        offset: usize,
+        kind: io::ErrorKind,
+    },
+    // A value that is too big for its field when encoding
+    FieldOverflow {
+        value: usize,
+        width: usize,
+    },
 }
 
-fn parse(bin: &str) -> Packet {
-    let mut pointer = 0;
-    _parse(bin, &mut pointer)
+impl fmt::Display for BitsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitsError::InvalidHex { offset, character } => {
+                write!(f, "invalid hex digit {:?} at bit {}", character, offset)
+            }
+            BitsError::Truncated { offset } => {
+                write!(f, "transmission ended unexpectedly at bit {}", offset)
+            }
+            BitsError::LengthMismatch { offset } => write!(
+                f,
+                "sub-packets did not match their declared length at bit {}",
+                offset
+            ),
+            BitsError::LiteralOverflow { offset } => {
+                write!(f, "literal value is too large at bit {}", offset)
+            }
+            BitsError::ValueOverflow { offset } => {
+                write!(f, "operator value is too large at bit {}", offset)
+            }
+            BitsError::UnknownOperator { offset, type_id } => {
+                write!(f, "unknown operator type id {} at bit {}", type_id, offset)
+            }
+            BitsError::OperandCount {
+                offset,
+                type_id,
+                count,
+            } => write!(
+                f,
+                "operator type id {} can't take {} sub-packets at bit {}",
+                type_id, count, offset
+            ),
+            BitsError::Io { offset, kind } => {
+                write!(f, "failed to read bit {}: {}", offset, kind)
+            }
+            BitsError::FieldOverflow { value, width } => {
+                write!(f, "{} does not fit in {} bits", value, width)
+            }
+        }
+    }
 }
 
-fn _parse(bin: &str, pointer: &mut usize) -> Packet {
-    let version = parse_bin(&bin[*pointer..*pointer + 3]);
-    *pointer += 3;
-    let type_id = parse_bin(&bin[*pointer..*pointer + 3]);
-    *pointer += 3;
+impl Error for BitsError {}
 
-    match type_id {
-        4 => {
-            let mut value_bits: Vec<char> = vec![];
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: usize) -> Result<usize, BitsError> {
+        if self.position + bits > self.bytes.len() * 8 {
+            return Err(BitsError::Truncated {
+                offset: self.position,
+            });
+        }
+
+        let mut value = 0;
+        let mut remaining = bits;
+
+        // Take as many bits as we can from each byte at a time
+        while remaining > 0 {
+            let offset = self.position % 8;
+            let take = remaining.min(8 - offset);
+            let byte = self.bytes[self.position / 8] as usize;
+
+            value = (value << take) | ((byte >> (8 - offset - take)) & ((1 << take) - 1));
+
+            self.position += take;
+            remaining -= take;
+        }
+
+        Ok(value)
+    }
+}
+
+// Receives the parts of a transmission as they are decoded,
+// so that we never need to hold the whole thing in memory.
+trait Visitor {
+    type Output;
+
+    // The literal's value comes as its hex digits, most significant first,
+    // so that it can be any length
+    fn literal(&mut self, offset: usize, version: usize, digits: &[u8]) -> Result<(), BitsError>;
+
+    // The offset is the bit the operator packet starts at
+    fn start_operator(
+        &mut self,
+        offset: usize,
+        version: usize,
+        type_id: usize,
+        length_type_id: usize,
+    ) -> Result<(), BitsError>;
+
+    fn end_operator(&mut self) -> Result<(), BitsError>;
+
+    fn finish(self) -> Self::Output;
+}
+
+// Reads bits from a stream of hex digits, decoding it a chunk at a time.
+struct HexStream<R> {
+    input: R,
+    chunk_digits: u64,
+    buffer: Vec<u8>,
+    chunk: Vec<u8>,
+    chunk_position: usize,
+    digits: usize,
+    position: usize,
+    // A digit from the end of the last chunk, waiting for the other half of its byte
+    nibble: Option<u8>,
+    // Whitespace is only allowed at the end of the input, so we hold on to the first
+    // whitespace character since the last digit in case another digit follows it
+    whitespace: Option<(usize, char)>,
+    // The digits of the literal being decoded, kept to avoid allocating one for each literal
+    literal: Vec<u8>,
+}
+
+const CHUNK_DIGITS: u64 = 1 << 16;
+
+impl<R: Read> HexStream<R> {
+    fn new(input: R) -> Self {
+        HexStream::with_chunk_digits(input, CHUNK_DIGITS)
+    }
+
+    fn with_chunk_digits(input: R, chunk_digits: u64) -> Self {
+        HexStream {
+            input,
+            chunk_digits,
+            buffer: vec![],
+            chunk: vec![],
+            chunk_position: 0,
+            digits: 0,
+            position: 0,
+            nibble: None,
+            whitespace: None,
+            literal: vec![],
+        }
+    }
+
+    // Returns false once the input is exhausted.
+    // Works on raw bytes, so anything that isn't a hex digit (even invalid UTF-8) is an InvalidHex.
+    fn refill(&mut self) -> Result<bool, BitsError> {
+        self.buffer.clear();
+        let read = (&mut self.input)
+            .take(self.chunk_digits)
+            .read_to_end(&mut self.buffer)
+            .map_err(|e| BitsError::Io {
+                offset: self.position,
+                kind: e.kind(),
+            })?;
+
+        self.chunk.clear();
+        self.chunk_position = 0;
+
+        for (i, &byte) in self.buffer.iter().enumerate() {
+            let offset = (self.digits + i) * 4;
+            let character = byte as char;
+
+            if character.is_ascii_whitespace() {
+                self.whitespace.get_or_insert((offset, character));
+                continue;
+            }
+            if let Some((offset, character)) = self.whitespace {
+                return Err(BitsError::InvalidHex { offset, character });
+            }
+
+            let digit = character
+                .to_digit(16)
+                .ok_or(BitsError::InvalidHex { offset, character })? as u8;
+            match self.nibble.take() {
+                Some(high) => self.chunk.push(high << 4 | digit),
+                None => self.nibble = Some(digit),
+            }
+        }
+        self.digits += read;
+
+        if read == 0 {
+            // An odd number of digits leaves half a byte at the very end
+            if let Some(high) = self.nibble.take() {
+                self.chunk.push(high << 4);
+            }
+            return Ok(!self.chunk.is_empty());
+        }
+
+        Ok(true)
+    }
+
+    fn read(&mut self, bits: usize) -> Result<usize, BitsError> {
+        let mut value = 0;
+        let mut remaining = bits;
+
+        while remaining > 0 {
+            let available = self.chunk.len() * 8 - self.chunk_position;
+
+            if available == 0 {
+                if !self.refill()? {
+                    return Err(BitsError::Truncated {
+                        offset: self.position,
+                    });
+                }
+                continue;
+            }
+
+            let take = remaining.min(available);
+            let mut reader = BitReader {
+                bytes: &self.chunk,
+                position: self.chunk_position,
+            };
+            value = (value << take) | reader.read(take)?;
+
+            self.chunk_position = reader.position;
+            self.position += take;
+            remaining -= take;
+        }
+
+        Ok(value)
+    }
+}
+
+// An operator whose sub-packets are still being decoded
+enum Open {
+    // Length type 0: the bit its sub-packets end at
+    Bits(usize),
+    // Length type 1: how many sub-packets are left to decode
+    Packets(usize),
+}
+
+// Operators are kept on a stack rather than decoded recursively, so that deeply
+// nested transmissions can't overflow the call stack.
+fn decode<R: Read, V: Visitor>(input: R, visitor: V) -> Result<V::Output, BitsError> {
+    decode_hex_stream(HexStream::new(input), visitor)
+}
+
+fn decode_hex_stream<R: Read, V: Visitor>(
+    mut stream: HexStream<R>,
+    mut visitor: V,
+) -> Result<V::Output, BitsError> {
+    let mut open = vec![];
+
+    loop {
+        let offset = stream.position;
+        let version = stream.read(3)?;
+        let type_id = stream.read(3)?;
+
+        if type_id == 4 {
+            let mut digits = std::mem::take(&mut stream.literal);
+            digits.clear();
 
             let mut another = true;
             while another {
-                another = &bin[*pointer..*pointer + 1] == "1";
-                *pointer += 1;
+                another = stream.read(1)? == 1;
+                digits.push(stream.read(4)? as u8);
+            }
 
-                bin[*pointer..*pointer + 4]
-                    .chars()
-                    .for_each(|c| value_bits.push(c));
-                *pointer += 4;
+            visitor.literal(offset, version, &digits)?;
+            stream.literal = digits;
+        } else {
+            let length_type_id = stream.read(1)?;
+
+            visitor.start_operator(offset, version, type_id, length_type_id)?;
+
+            // The length type is a single bit, so it can only be 0 or 1
+            open.push(if length_type_id == 0 {
+                let num_bits = stream.read(15)?;
+                Open::Bits(stream.position + num_bits)
+            } else {
+                Open::Packets(stream.read(11)?)
+            });
+        }
+
+        // Close every operator that this packet was the last sub-packet of
+        loop {
+            match open.last_mut() {
+                None => return Ok(visitor.finish()),
+                Some(Open::Bits(end)) if stream.position < *end => break,
+                Some(Open::Bits(end)) if stream.position > *end => {
+                    return Err(BitsError::LengthMismatch {
+                        offset: stream.position,
+                    })
+                }
+                Some(Open::Packets(remaining)) if *remaining > 0 => {
+                    *remaining -= 1;
+                    break;
+                }
+                _ => {
+                    open.pop();
+                    visitor.end_operator()?;
+                }
             }
+        }
+    }
+}
 
-            let value: String = value_bits.into_iter().collect();
+impl Packet {
+    // Replays the packet into a visitor, as if it were being decoded.
+    // Offsets are where each packet would be in the packet's encoding, which is the same as
+    // the transmission it was decoded from unless that padded its literals with extra groups.
+    fn accept<V: Visitor>(&self, mut visitor: V) -> Result<V::Output, BitsError> {
+        self._accept(&mut visitor, &mut 0)?;
+        Ok(visitor.finish())
+    }
 
-            Packet::Literal {
+    fn _accept(&self, visitor: &mut impl Visitor, position: &mut usize) -> Result<(), BitsError> {
+        match self {
+            Packet::Literal { version, value, .. } => {
+                let digits = value.to_radix_be(16);
+                let offset = *position;
+                *position += 6 + 5 * digits.len();
+                visitor.literal(offset, *version, &digits)
+            }
+            Packet::Operator {
                 version,
                 type_id,
-                value: parse_bin(&value),
+                length_type_id,
+                packets,
+            } => {
+                visitor.start_operator(*position, *version, *type_id, *length_type_id)?;
+                *position += 7 + if *length_type_id == 0 { 15 } else { 11 };
+                packets
+                    .iter()
+                    .try_for_each(|p| p._accept(visitor, position))?;
+                visitor.end_operator()
             }
         }
-        _ => {
-            let length_type_id = parse_bin(&bin[*pointer..*pointer + 1]);
-            *pointer += 1;
+    }
+}
+
+#[derive(Default)]
+struct PacketBuilder {
+    // The operators we're in the middle of
+    stack: Vec<Packet>,
+    packet: Option<Packet>,
+}
+
+impl PacketBuilder {
+    fn push(&mut self, packet: Packet) {
+        match self.stack.last_mut() {
+            Some(Packet::Operator { packets, .. }) => packets.push(packet),
+            _ => self.packet = Some(packet),
+        }
+    }
+}
 
-            match length_type_id {
-                0 => {
-                    let num_bits = parse_bin(&bin[*pointer..*pointer + 15]);
-                    *pointer += 15;
+impl Visitor for PacketBuilder {
+    type Output = Packet;
 
-                    let current_pointer = *pointer;
+    fn literal(&mut self, _offset: usize, version: usize, digits: &[u8]) -> Result<(), BitsError> {
+        self.push(Packet::Literal {
+            version,
+            type_id: 4,
+            value: BigUint::from_radix_be(digits, 16).unwrap(),
+        });
+        Ok(())
+    }
 
-                    let mut packets = vec![];
+    fn start_operator(
+        &mut self,
+        _offset: usize,
+        version: usize,
+        type_id: usize,
+        length_type_id: usize,
+    ) -> Result<(), BitsError> {
+        self.stack.push(Packet::Operator {
+            version,
+            type_id,
+            length_type_id,
+            packets: vec![],
+        });
+        Ok(())
+    }
 
-                    while *pointer != current_pointer + num_bits {
-                        packets.push(_parse(bin, pointer));
-                    }
+    fn end_operator(&mut self) -> Result<(), BitsError> {
+        let packet = self.stack.pop().unwrap();
+        self.push(packet);
+        Ok(())
+    }
 
-                    Packet::Operator {
-                        version,
-                        type_id,
-                        length_type_id,
-                        packets,
-                    }
-                }
-                1 => {
-                    let num_packets = parse_bin(&bin[*pointer..*pointer + 11]);
-                    *pointer += 11;
+    fn finish(self) -> Packet {
+        // The decoder only finishes after a whole packet
+        self.packet.unwrap()
+    }
+}
+
+#[derive(Default)]
+struct VersionSum {
+    sum: usize,
+}
+
+impl Visitor for VersionSum {
+    type Output = usize;
+
+    fn literal(&mut self, _offset: usize, version: usize, _digits: &[u8]) -> Result<(), BitsError> {
+        self.sum += version;
+        Ok(())
+    }
+
+    fn start_operator(
+        &mut self,
+        _offset: usize,
+        version: usize,
+        _type_id: usize,
+        _length_type_id: usize,
+    ) -> Result<(), BitsError> {
+        self.sum += version;
+        Ok(())
+    }
+
+    fn end_operator(&mut self) -> Result<(), BitsError> {
+        Ok(())
+    }
+
+    fn finish(self) -> usize {
+        self.sum
+    }
+}
+
+// An operator whose sub-packets are folded into it as they arrive
+struct Operation<N> {
+    offset: usize,
+    type_id: usize,
+    value: Option<N>,
+    count: usize,
+}
+
+impl<N: Num + Ord + Clone + From<usize> + CheckedAdd + CheckedMul> Operation<N> {
+    fn combine(&mut self, value: N) -> Result<(), BitsError> {
+        let compare = |result: bool| N::from(result as usize);
+        let overflow = BitsError::ValueOverflow {
+            offset: self.offset,
+        };
+
+        self.value = Some(match (self.type_id, self.value.take()) {
+            (_, None) => value,
+            (0, Some(v)) => v.checked_add(&value).ok_or(overflow)?,
+            (1, Some(v)) => v.checked_mul(&value).ok_or(overflow)?,
+            (2, Some(v)) => v.min(value),
+            (3, Some(v)) => v.max(value),
+            (5, Some(v)) if self.count == 1 => compare(v > value),
+            (6, Some(v)) if self.count == 1 => compare(v < value),
+            (7, Some(v)) if self.count == 1 => compare(v == value),
+            // Extra operands and unknown operators are reported by finish
+            (_, Some(v)) => v,
+        });
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<N, BitsError> {
+        let operand_count = BitsError::OperandCount {
+            offset: self.offset,
+            type_id: self.type_id,
+            count: self.count,
+        };
+
+        match (self.type_id, self.value) {
+            (0, v) => Ok(v.unwrap_or_else(N::zero)),
+            (1, v) => Ok(v.unwrap_or_else(N::one)),
+            (2 | 3, v) => v.ok_or(operand_count),
+            (5..=7, Some(v)) if self.count == 2 => Ok(v),
+            (5..=7, _) => Err(operand_count),
+            _ => Err(BitsError::UnknownOperator {
+                offset: self.offset,
+                type_id: self.type_id,
+            }),
+        }
+    }
+}
+
+// Generic so that transmissions too large for a usize can be evaluated with a BigUint
+struct Evaluator<N> {
+    stack: Vec<Operation<N>>,
+    value: Option<N>,
+}
+
+impl<N> Default for Evaluator<N> {
+    fn default() -> Self {
+        Evaluator {
+            stack: vec![],
+            value: None,
+        }
+    }
+}
+
+impl<N: Num + Ord + Clone + From<usize> + CheckedAdd + CheckedMul> Evaluator<N> {
+    fn push(&mut self, value: N) -> Result<(), BitsError> {
+        match self.stack.last_mut() {
+            Some(operation) => operation.combine(value)?,
+            None => self.value = Some(value),
+        }
+        Ok(())
+    }
+}
+
+impl<N: Num + Ord + Clone + From<usize> + CheckedAdd + CheckedMul> Visitor for Evaluator<N> {
+    type Output = N;
+
+    // Literals too large for N are an error, rather than wrapping around
+    fn literal(&mut self, offset: usize, _version: usize, digits: &[u8]) -> Result<(), BitsError> {
+        let value = digits
+            .iter()
+            .try_fold(N::zero(), |value, &digit| {
+                value
+                    .checked_mul(&N::from(16))?
+                    .checked_add(&N::from(digit as usize))
+            })
+            .ok_or(BitsError::LiteralOverflow { offset })?;
+
+        self.push(value)
+    }
+
+    fn start_operator(
+        &mut self,
+        offset: usize,
+        _version: usize,
+        type_id: usize,
+        _length_type_id: usize,
+    ) -> Result<(), BitsError> {
+        self.stack.push(Operation {
+            offset,
+            type_id,
+            value: None,
+            count: 0,
+        });
+        Ok(())
+    }
+
+    fn end_operator(&mut self) -> Result<(), BitsError> {
+        let value = self.stack.pop().unwrap().finish()?;
+        self.push(value)
+    }
+
+    fn finish(self) -> N {
+        self.value.unwrap()
+    }
+}
+
+fn sum_versions(packet: &Packet) -> Result<usize, BitsError> {
+    packet.accept(VersionSum::default())
+}
 
-                    let packets = (0..num_packets).map(|_| _parse(bin, pointer)).collect();
+fn evaluate<N: Num + Ord + Clone + From<usize> + CheckedAdd + CheckedMul>(
+    packet: &Packet,
+) -> Result<N, BitsError> {
+    packet.accept(Evaluator::default())
+}
 
-                    Packet::Operator {
-                        version,
-                        type_id,
-                        length_type_id,
-                        packets,
+fn operator_name(type_id: usize) -> String {
+    match type_id {
+        0 => "sum".to_string(),
+        1 => "product".to_string(),
+        2 => "min".to_string(),
+        3 => "max".to_string(),
+        5 => "gt".to_string(),
+        6 => "lt".to_string(),
+        7 => "eq".to_string(),
+        _ => format!("op{}", type_id),
+    }
+}
+
+impl Packet {
+    // Writes the packet as nested function calls,
+    // with each sub-packet on its own line if a depth is given.
+    fn write_expression(&self, f: &mut fmt::Formatter<'_>, depth: Option<usize>) -> fmt::Result {
+        match self {
+            Packet::Literal { value, .. } => write!(f, "{}", value),
+            Packet::Operator {
+                type_id, packets, ..
+            } => {
+                write!(f, "{}(", operator_name(*type_id))?;
+
+                match depth {
+                    None => packets.iter().enumerate().try_for_each(|(i, p)| {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        p.write_expression(f, None)
+                    })?,
+                    Some(depth) => {
+                        packets.iter().try_for_each(|p| {
+                            write!(f, "\n{}", "    ".repeat(depth + 1))?;
+                            p.write_expression(f, Some(depth + 1))?;
+                            write!(f, ",")
+                        })?;
+
+                        if !packets.is_empty() {
+                            write!(f, "\n{}", "    ".repeat(depth))?;
+                        }
                     }
                 }
-                _ => unreachable!("Length type id was not in binary"),
+
+                write!(f, ")")
             }
         }
     }
 }
 
-fn sum_versions(packet: &Packet) -> usize {
-    match packet {
-        Packet::Literal { version, .. } => *version,
-        Packet::Operator {
-            version, packets, ..
-        } => version + packets.iter().map(sum_versions).sum::<usize>(),
+// Use {:#} for the indented tree form.
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_expression(f, f.alternate().then_some(0))
     }
 }
 
-fn evaluate(packet: &Packet) -> usize {
+// Encoding lets us build our own transmissions.
+pub fn to_bin(value: usize, width: usize) -> Result<String, BitsError> {
+    if width < usize::BITS as usize && value >> width != 0 {
+        return Err(BitsError::FieldOverflow { value, width });
+    }
+
+    Ok(format!("{:0width$b}", value, width = width))
+}
+
+// Encodes the packet as a string of '0' and '1' characters.
+pub fn encode(packet: &Packet) -> Result<String, BitsError> {
     match packet {
-        Packet::Literal { value, .. } => *value,
+        Packet::Literal {
+            version,
+            type_id,
+            value,
+        } => {
+            // Every group but the last is prefixed with a 1
+            let digits = value.to_radix_be(16);
+            let value_bits: String = digits
+                .iter()
+                .enumerate()
+                .map(|(g, digit)| {
+                    let prefix = if g == digits.len() - 1 { "0" } else { "1" };
+                    format!("{}{:04b}", prefix, digit)
+                })
+                .collect();
+
+            Ok(to_bin(*version, 3)? + &to_bin(*type_id, 3)? + &value_bits)
+        }
         Packet::Operator {
-            type_id, packets, ..
+            version,
+            type_id,
+            length_type_id,
+            packets,
         } => {
-            let mut values = packets.iter().map(evaluate);
-            match type_id {
-                0 => values.sum(),
-                1 => values.product(),
-                2 => values.min().unwrap(),
-                3 => values.max().unwrap(),
-                5 => {
-                    if values.next().unwrap() > values.next().unwrap() {
-                        1
-                    } else {
-                        0
-                    }
-                }
-                6 => {
-                    if values.next().unwrap() < values.next().unwrap() {
-                        1
-                    } else {
-                        0
-                    }
-                }
-                7 => {
-                    if values.next().unwrap() == values.next().unwrap() {
-                        1
-                    } else {
-                        0
-                    }
-                }
-                _ => unreachable!("Unknown operator type id {}", type_id),
-            }
+            let sub_bits: String = packets.iter().map(encode).try_collect()?;
+
+            let length = match length_type_id {
+                0 => to_bin(sub_bits.len(), 15)?,
+                _ => to_bin(packets.len(), 11)?,
+            };
+
+            Ok(to_bin(*version, 3)?
+                + &to_bin(*type_id, 3)?
+                + &to_bin(*length_type_id, 1)?
+                + &length
+                + &sub_bits)
         }
     }
 }
 
-fn hex_to_bin(hex: &str) -> String {
-    hex.chars()
-        .map(|c| match c {
-            '0' => "0000",
-            '1' => "0001",
-            '2' => "0010",
-            '3' => "0011",
-            '4' => "0100",
-            '5' => "0101",
-            '6' => "0110",
-            '7' => "0111",
-            '8' => "1000",
-            '9' => "1001",
-            'A' => "1010",
-            'B' => "1011",
-            'C' => "1100",
-            'D' => "1101",
-            'E' => "1110",
-            'F' => "1111",
-            _ => "",
+fn bin_to_hex(bin: &str) -> String {
+    // Pad with zeros out to a whole number of bytes, like the puzzle transmissions
+    let padded = bin.to_string() + &"0".repeat((8 - bin.len() % 8) % 8);
+
+    padded
+        .as_bytes()
+        .chunks(4)
+        .map(|digit| {
+            let value = digit.iter().fold(0, |v, &b| v << 1 | (b == b'1') as usize);
+            format!("{:X}", value)
         })
         .collect()
 }
 
+// Encodes the packet as a hex transmission, like the puzzle input.
+pub fn encode_hex(packet: &Packet) -> Result<String, BitsError> {
+    encode(packet).map(|bin| bin_to_hex(&bin))
+}
+
 pub fn solve() -> SolverResult {
-    let bin = hex_to_bin(&read_to_string("data/day_16.txt")?);
+    let packet = decode(File::open("data/day_16.txt")?, PacketBuilder::default())?;
 
-    let bin_argument = &bin;
-    println!("Part 1: {}", sum_versions(&parse(bin_argument)));
-    let bin_argument = &bin;
-    println!("Part 2: {}", evaluate(&parse(bin_argument)));
+    println!("Part 1: {}", sum_versions(&packet)?);
+    println!("Part 2: {}", evaluate::<BigUint>(&packet)?);
 
     Ok(())
 }
@@ -183,17 +747,753 @@ pub fn solve() -> SolverResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
+
+    fn parse(hex: &str) -> Result<Packet, BitsError> {
+        decode(hex.as_bytes(), PacketBuilder::default())
+    }
+
+    fn decode_hex(hex: &str) -> Packet {
+        parse(hex).unwrap()
+    }
+
+    // The original parser, which works on a string of '0' and '1' characters.
+    fn hex_to_bin(hex: &str) -> String {
+        hex.chars()
+            .map(|c| match c {
+                '0' => "0000",
+                '1' => "0001",
+                '2' => "0010",
+                '3' => "0011",
+                '4' => "0100",
+                '5' => "0101",
+                '6' => "0110",
+                '7' => "0111",
+                '8' => "1000",
+                '9' => "1001",
+                'A' => "1010",
+                'B' => "1011",
+                'C' => "1100",
+                'D' => "1101",
+                'E' => "1110",
+                'F' => "1111",
+                _ => "",
+            })
+            .collect()
+    }
+
+    fn parse_bin(bin: &str) -> usize {
+        usize::from_str_radix(bin, 2).unwrap()
+    }
+
+    fn parse_str(bin: &str) -> Packet {
+        let mut pointer = 0;
+        _parse_str(bin, &mut pointer)
+    }
+
+    fn _parse_str(bin: &str, pointer: &mut usize) -> Packet {
+        let version = parse_bin(&bin[*pointer..*pointer + 3]);
+        *pointer += 3;
+        let type_id = parse_bin(&bin[*pointer..*pointer + 3]);
+        *pointer += 3;
+
+        match type_id {
+            4 => {
+                let mut value_bits: Vec<char> = vec![];
+
+                let mut another = true;
+                while another {
+                    another = &bin[*pointer..*pointer + 1] == "1";
+                    *pointer += 1;
+
+                    bin[*pointer..*pointer + 4]
+                        .chars()
+                        .for_each(|c| value_bits.push(c));
+                    *pointer += 4;
+                }
+
+                let value: String = value_bits.into_iter().collect();
+
+                Packet::Literal {
+                    version,
+                    type_id,
+                    value: BigUint::from(parse_bin(&value)),
+                }
+            }
+            _ => {
+                let length_type_id = parse_bin(&bin[*pointer..*pointer + 1]);
+                *pointer += 1;
+
+                match length_type_id {
+                    0 => {
+                        let num_bits = parse_bin(&bin[*pointer..*pointer + 15]);
+                        *pointer += 15;
+
+                        let current_pointer = *pointer;
+
+                        let mut packets = vec![];
+
+                        while *pointer != current_pointer + num_bits {
+                            packets.push(_parse_str(bin, pointer));
+                        }
+
+                        Packet::Operator {
+                            version,
+                            type_id,
+                            length_type_id,
+                            packets,
+                        }
+                    }
+                    1 => {
+                        let num_packets = parse_bin(&bin[*pointer..*pointer + 11]);
+                        *pointer += 11;
+
+                        let packets = (0..num_packets).map(|_| _parse_str(bin, pointer)).collect();
+
+                        Packet::Operator {
+                            version,
+                            type_id,
+                            length_type_id,
+                            packets,
+                        }
+                    }
+                    _ => unreachable!("Length type id was not in binary"),
+                }
+            }
+        }
+    }
+
+    const EXAMPLES: [&str; 15] = [
+        "D2FE28",
+        "38006F45291200",
+        "EE00D40C823060",
+        "8A004A801A8002F478",
+        "620080001611562C8802118E34",
+        "C0015000016115A2E0802F182340",
+        "A0016C880162017C3686B18A3D4780",
+        "C200B40A82",
+        "04005AC33890",
+        "880086C3E88112",
+        "CE00C43D881120",
+        "D8005AC2A8F0",
+        "F600BC2D8F",
+        "9C005AC2F8F0",
+        "9C0141080250320F1802104A08",
+    ];
+
+    #[test]
+    fn encode_round_trips() {
+        EXAMPLES.iter().for_each(|&hex| {
+            let packet = decode_hex(hex);
+            let encoded = encode_hex(&packet).unwrap();
+
+            assert_eq!(decode_hex(&encoded), packet, "{}", hex);
+            assert_eq!(encoded, hex);
+        });
+    }
+
+    #[test]
+    fn bit_reader_matches_string_parser() {
+        EXAMPLES.iter().for_each(|&hex| {
+            assert_eq!(decode_hex(hex), parse_str(&hex_to_bin(hex)));
+        });
+    }
+
+    // A transmission with a couple million literals, nested a few levels deep.
+    fn large_transmission() -> String {
+        let literal = |value: usize| Packet::Literal {
+            version: 1,
+            type_id: 4,
+            value: BigUint::from(value),
+        };
+        let operator = |type_id, packets| Packet::Operator {
+            version: 2,
+            type_id,
+            length_type_id: 1,
+            packets,
+        };
+
+        let packet = operator(
+            0,
+            (0..1000)
+                .map(|i| operator(3, (0..2000).map(|j| literal(i * j)).collect()))
+                .collect(),
+        );
+
+        encode_hex(&packet).unwrap()
+    }
+
+    // Run with cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_bit_reader() {
+        let hex = large_transmission();
+
+        let before = Instant::now();
+        let bin = hex_to_bin(&hex);
+        let from_str = parse_str(&bin);
+        let str_elapsed = before.elapsed();
+
+        let before = Instant::now();
+        let from_bytes = decode_hex(&hex);
+        let bytes_elapsed = before.elapsed();
+
+        assert_eq!(from_str, from_bytes);
+
+        println!(
+            "{} hex digits: string of {} bits in {:.2?}, bit reader in {:.2?}",
+            hex.len(),
+            bin.len(),
+            str_elapsed,
+            bytes_elapsed
+        );
+    }
+
+    #[test]
+    fn encode_new_transmission() {
+        let literal = |value: usize| Packet::Literal {
+            version: 5,
+            type_id: 4,
+            value: BigUint::from(value),
+        };
+        let packet = Packet::Operator {
+            version: 1,
+            type_id: 0,
+            length_type_id: 1,
+            packets: vec![
+                Packet::Operator {
+                    version: 7,
+                    type_id: 1,
+                    length_type_id: 0,
+                    packets: vec![literal(3), literal(4)],
+                },
+                literal(0x1234),
+            ],
+        };
+
+        let hex = encode_hex(&packet).unwrap();
+        let parsed = decode_hex(&hex);
+
+        assert_eq!(parsed, packet);
+        assert_eq!(sum_versions(&parsed), Ok(1 + 7 + 5 * 3));
+        assert_eq!(evaluate::<usize>(&parsed), Ok(3 * 4 + 0x1234));
+    }
+
+    #[test]
+    fn encode_overflow() {
+        let literal = |version| Packet::Literal {
+            version,
+            type_id: 4,
+            value: BigUint::from(1u32),
+        };
+        let operator = |type_id, length_type_id, packets| Packet::Operator {
+            version: 0,
+            type_id,
+            length_type_id,
+            packets,
+        };
+
+        assert_eq!(
+            encode(&literal(8)),
+            Err(BitsError::FieldOverflow { value: 8, width: 3 })
+        );
+        assert_eq!(
+            encode(&operator(8, 1, vec![])),
+            Err(BitsError::FieldOverflow { value: 8, width: 3 })
+        );
+        assert_eq!(
+            encode(&operator(0, 2, vec![])),
+            Err(BitsError::FieldOverflow { value: 2, width: 1 })
+        );
+
+        // Each literal takes 11 bits, so 2979 of them overflow the 15 bit length
+        assert_eq!(
+            encode(&operator(0, 0, (0..2979).map(|_| literal(0)).collect())),
+            Err(BitsError::FieldOverflow {
+                value: 32769,
+                width: 15
+            })
+        );
+        assert!(encode(&operator(0, 0, (0..2978).map(|_| literal(0)).collect())).is_ok());
+
+        assert_eq!(
+            encode(&operator(0, 1, (0..2048).map(|_| literal(0)).collect())),
+            Err(BitsError::FieldOverflow {
+                value: 2048,
+                width: 11
+            })
+        );
+        assert!(encode(&operator(0, 1, (0..2047).map(|_| literal(0)).collect())).is_ok());
+
+        assert_eq!(
+            encode(&literal(9)).unwrap_err().to_string(),
+            "9 does not fit in 3 bits"
+        );
+    }
+
+    // Streams sum(sum(...sum(1, 1, ...))) as hex, with the given number of levels
+    // of operators that each have width sub-packets, a few bits at a time.
+    struct SyntheticStream {
+        levels: usize,
+        width: usize,
+        // How many sub-packets are left for each operator we're inside of
+        remaining: Vec<usize>,
+        started: bool,
+        finished: bool,
+        bits: u64,
+        length: usize,
+        emitted: usize,
+    }
+
+    impl SyntheticStream {
+        fn new(levels: usize, width: usize) -> Self {
+            SyntheticStream {
+                levels,
+                width,
+                remaining: vec![],
+                started: false,
+                finished: false,
+                bits: 0,
+                length: 0,
+                emitted: 0,
+            }
+        }
+
+        fn push_bits(&mut self, value: u64, length: usize) {
+            self.bits = (self.bits << length) | value;
+            self.length += length;
+            self.emitted += length;
+        }
+
+        fn push_operator(&mut self) {
+            // Version 1, sum, counting sub-packets
+            self.push_bits(0b0010001, 7);
+            self.push_bits(self.width as u64, 11);
+            self.remaining.push(self.width);
+        }
+
+        // Buffers the next packet header or literal, returning false if there are none left
+        fn advance(&mut self) -> bool {
+            if !self.started {
+                self.started = true;
+                self.push_operator();
+                return true;
+            }
+
+            while self.remaining.last() == Some(&0) {
+                self.remaining.pop();
+            }
+
+            match self.remaining.last_mut() {
+                None => false,
+                Some(r) => {
+                    *r -= 1;
+                    if self.remaining.len() < self.levels {
+                        self.push_operator();
+                    } else {
+                        // Version 1, literal 1
+                        self.push_bits(0b00110000001, 11);
+                    }
+                    true
+                }
+            }
+        }
+    }
+
+    impl Read for SyntheticStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut n = 0;
+
+            while n < buf.len() {
+                while self.length < 4 && !self.finished {
+                    if !self.advance() {
+                        self.finished = true;
+                        self.push_bits(0, (8 - self.emitted % 8) % 8);
+                    }
+                }
+
+                if self.length < 4 {
+                    break;
+                }
+
+                self.length -= 4;
+                let digit = (self.bits >> self.length) & 0xF;
+                self.bits &= (1 << self.length) - 1;
+
+                buf[n] = b"0123456789ABCDEF"[digit as usize];
+                n += 1;
+            }
+
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn decode_stream() {
+        // Long enough to need several chunks
+        let (levels, width) = (3, 50);
+        let operators = 1 + 50 + 50 * 50;
+
+        assert_eq!(
+            decode(SyntheticStream::new(levels, width), VersionSum::default()),
+            Ok(operators + 50 * 50 * 50)
+        );
+        assert_eq!(
+            decode(
+                SyntheticStream::new(levels, width),
+                Evaluator::<usize>::default()
+            ),
+            Ok(50 * 50 * 50)
+        );
+
+        let mut small = String::new();
+        SyntheticStream::new(2, 2)
+            .read_to_string(&mut small)
+            .unwrap();
+        assert_eq!(decode_hex(&small).to_string(), "sum(sum(1, 1), sum(1, 1))");
+    }
+
+    #[test]
+    fn deeply_nested() {
+        // Sums of a single sub-packet, 200,000 deep, around the literal 5
+        let levels = 200_000;
+        let sum = to_bin(1, 3).unwrap() + "000" + "1" + &to_bin(1, 11).unwrap();
+        let hex = bin_to_hex(&(sum.repeat(levels) + "001" + "100" + "00101"));
+
+        assert_eq!(
+            decode(hex.as_bytes(), VersionSum::default()),
+            Ok(levels + 1)
+        );
+        assert_eq!(decode(hex.as_bytes(), Evaluator::<usize>::default()), Ok(5));
+    }
+
+    // Run with cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn stress_decode_stream() {
+        // About 3 GB of hex
+        let (levels, width) = (3, 1024);
+
+        let before = Instant::now();
+        let value = decode(
+            SyntheticStream::new(levels, width),
+            Evaluator::<usize>::default(),
+        );
+        println!("Evaluated {:?} in {:.2?}", value, before.elapsed());
+
+        assert_eq!(value, Ok(1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn stream_errors() {
+        let input = "0".repeat(CHUNK_DIGITS as usize + 1) + "G";
+        let mut stream = HexStream::new(input.as_bytes());
+        assert_eq!(
+            (0..).try_for_each(|_| stream.read(4).map(|_| ())),
+            Err(BitsError::InvalidHex {
+                offset: (CHUNK_DIGITS as usize + 1) * 4,
+                character: 'G'
+            })
+        );
+
+        struct Broken;
+
+        impl Read for Broken {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::ConnectionReset))
+            }
+        }
+
+        assert_eq!(
+            decode(Broken, VersionSum::default()),
+            Err(BitsError::Io {
+                offset: 0,
+                kind: io::ErrorKind::ConnectionReset
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_hex() {
+        assert_eq!(
+            parse("D2FG28"),
+            Err(BitsError::InvalidHex {
+                offset: 12,
+                character: 'G'
+            })
+        );
+        assert_eq!(
+            parse("D2 FE28"),
+            Err(BitsError::InvalidHex {
+                offset: 8,
+                character: ' '
+            })
+        );
+        assert_eq!(
+            decode(&b"D2\xFFE28"[..], PacketBuilder::default()),
+            Err(BitsError::InvalidHex {
+                offset: 8,
+                character: '\u{FF}'
+            })
+        );
+        assert_eq!(parse("D2FE28\n"), parse("D2FE28"));
+    }
+
+    fn decode_in_chunks(hex: &str, chunk_digits: u64) -> Result<Packet, BitsError> {
+        decode_hex_stream(
+            HexStream::with_chunk_digits(hex.as_bytes(), chunk_digits),
+            PacketBuilder::default(),
+        )
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        // Chunks that end halfway through a byte, or next to whitespace, don't change anything
+        (1..=7).for_each(|chunk_digits| {
+            EXAMPLES.iter().for_each(|&hex| {
+                assert_eq!(
+                    decode_in_chunks(hex, chunk_digits),
+                    parse(hex),
+                    "{} in chunks of {}",
+                    hex,
+                    chunk_digits
+                );
+                assert_eq!(
+                    decode_in_chunks(&format!("{} \r\n", hex), chunk_digits),
+                    parse(hex)
+                );
+            });
+
+            // An odd number of digits is only padded at the very end
+            assert_eq!(
+                decode_in_chunks("302", chunk_digits),
+                Ok(Packet::Literal {
+                    version: 1,
+                    type_id: 4,
+                    value: BigUint::from(1u32)
+                })
+            );
+            assert_eq!(
+                decode_in_chunks("D2 FE28", chunk_digits),
+                Err(BitsError::InvalidHex {
+                    offset: 8,
+                    character: ' '
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn truncated_transmissions() {
+        // The literal 2021 needs 21 bits
+        assert_eq!(parse("D2FE"), Err(BitsError::Truncated { offset: 16 }));
+        assert_eq!(parse(""), Err(BitsError::Truncated { offset: 0 }));
+
+        // An operator that says it has two sub-packets, but only has one
+        let bin = "0010001".to_string() + "00000000010" + "10010000101";
+        assert_eq!(
+            parse(&bin_to_hex(&bin)),
+            Err(BitsError::Truncated { offset: 32 })
+        );
+    }
+
+    #[test]
+    fn length_mismatch() {
+        // Declares 10 bits of sub-packets, but the literal inside takes 11
+        let bin = "0010000".to_string() + "000000000001010" + "10010000101";
+        assert_eq!(
+            parse(&bin_to_hex(&bin)),
+            Err(BitsError::LengthMismatch { offset: 33 })
+        );
+    }
+
+    #[test]
+    fn wide_literals() {
+        // 17 groups of 1111 is 2^68 - 1, which is too big for a usize
+        let bin = "100100".to_string() + &"11111".repeat(16) + "01111";
+        let hex = bin_to_hex(&bin);
+        let packet = decode_hex(&hex);
+        let value = BigUint::from(2usize).pow(68) - 1usize;
+
+        assert_eq!(
+            packet,
+            Packet::Literal {
+                version: 4,
+                type_id: 4,
+                value: value.clone()
+            }
+        );
+        assert_eq!(encode_hex(&packet).unwrap(), hex);
+        assert_eq!(evaluate::<BigUint>(&packet), Ok(value.clone()));
+        assert_eq!(
+            evaluate::<usize>(&packet),
+            Err(BitsError::LiteralOverflow { offset: 0 })
+        );
+
+        // The overflow is reported at the start of the literal, while decoding too
+        let packet = Packet::Operator {
+            version: 0,
+            type_id: 0,
+            length_type_id: 1,
+            packets: vec![packet],
+        };
+        assert_eq!(
+            decode(
+                encode_hex(&packet).unwrap().as_bytes(),
+                Evaluator::<usize>::default()
+            ),
+            Err(BitsError::LiteralOverflow { offset: 18 })
+        );
+        assert_eq!(
+            decode(
+                encode_hex(&packet).unwrap().as_bytes(),
+                Evaluator::<BigUint>::default()
+            ),
+            Ok(value)
+        );
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        let literal = || Packet::Literal {
+            version: 0,
+            type_id: 4,
+            value: BigUint::from(1u32),
+        };
+        let operator = |type_id, packets| Packet::Operator {
+            version: 0,
+            type_id,
+            length_type_id: 1,
+            packets,
+        };
+
+        assert_eq!(
+            evaluate::<usize>(&operator(2, vec![])),
+            Err(BitsError::OperandCount {
+                offset: 0,
+                type_id: 2,
+                count: 0
+            })
+        );
+        assert_eq!(
+            evaluate::<usize>(&operator(4, vec![])),
+            Err(BitsError::UnknownOperator {
+                offset: 0,
+                type_id: 4
+            })
+        );
+
+        // The comparison starts after the sum's 18 bit header and an 11 bit literal
+        let packet = operator(0, vec![literal(), operator(7, vec![literal()])]);
+        assert_eq!(
+            evaluate::<usize>(&packet),
+            Err(BitsError::OperandCount {
+                offset: 29,
+                type_id: 7,
+                count: 1
+            })
+        );
+
+        // The offsets are the same when evaluating while decoding
+        let hex = encode_hex(&packet).unwrap();
+        assert_eq!(
+            decode(hex.as_bytes(), Evaluator::<usize>::default()),
+            Err(BitsError::OperandCount {
+                offset: 29,
+                type_id: 7,
+                count: 1
+            })
+        );
+        assert_eq!(
+            evaluate::<usize>(&packet).unwrap_err().to_string(),
+            "operator type id 7 can't take 1 sub-packets at bit 29"
+        );
+    }
+
+    #[test]
+    fn display_expressions() {
+        let packet = decode_hex("9C0141080250320F1802104A08");
+
+        assert_eq!(packet.to_string(), "eq(sum(1, 3), product(2, 2))");
+        assert_eq!(
+            format!("{:#}", packet),
+            "\
+eq(
+    sum(
+        1,
+        3,
+    ),
+    product(
+        2,
+        2,
+    ),
+)"
+        );
+
+        assert_eq!(decode_hex("880086C3E88112").to_string(), "min(7, 8, 9)");
+    }
+
+    #[test]
+    fn evaluate_big() {
+        let literal = |value: usize| Packet::Literal {
+            version: 0,
+            type_id: 4,
+            value: BigUint::from(value),
+        };
+        let packet = Packet::Operator {
+            version: 0,
+            type_id: 1,
+            length_type_id: 1,
+            packets: (0..5).map(|_| literal(1 << 40)).collect(),
+        };
+        let packet = decode_hex(&encode_hex(&packet).unwrap());
+
+        assert_eq!(
+            evaluate::<BigUint>(&packet),
+            Ok(BigUint::from(2usize).pow(200))
+        );
+        assert_eq!(
+            evaluate::<usize>(&packet),
+            Err(BitsError::ValueOverflow { offset: 0 })
+        );
+
+        // max(1, sum(usize::MAX, 1)): the sum comes after an 18 bit header and an 11 bit literal
+        let sum = Packet::Operator {
+            version: 0,
+            type_id: 0,
+            length_type_id: 1,
+            packets: vec![literal(usize::MAX), literal(1)],
+        };
+        let packet = Packet::Operator {
+            version: 0,
+            type_id: 3,
+            length_type_id: 1,
+            packets: vec![literal(1), sum],
+        };
+        assert_eq!(
+            evaluate::<usize>(&packet),
+            Err(BitsError::ValueOverflow { offset: 29 })
+        );
+        assert_eq!(
+            evaluate::<BigUint>(&packet),
+            Ok(BigUint::from(usize::MAX) + 1usize)
+        );
+        assert_eq!(
+            evaluate::<BigUint>(&decode_hex("9C0141080250320F1802104A08")),
+            Ok(BigUint::from(1usize))
+        );
+    }
 
     #[test]
     fn part_1_example_1() {
         let bin = hex_to_bin("D2FE28");
         assert_eq!(bin, "110100101111111000101000");
         assert_eq!(
-            parse(&bin),
+            decode_hex("D2FE28"),
             Packet::Literal {
                 version: 6,
                 type_id: 4,
-                value: 2021
+                value: BigUint::from(2021u32)
             }
         );
     }
@@ -205,7 +1505,7 @@ mod tests {
             "00111000000000000110111101000101001010010001001000000000"
         );
         assert_eq!(
-            parse(&bin),
+            decode_hex("38006F45291200"),
             Packet::Operator {
                 version: 1,
                 type_id: 6,
@@ -214,12 +1514,12 @@ mod tests {
                     Packet::Literal {
                         version: 6,
                         type_id: 4,
-                        value: 10
+                        value: BigUint::from(10u32)
                     },
                     Packet::Literal {
                         version: 2,
                         type_id: 4,
-                        value: 20
+                        value: BigUint::from(20u32)
                     }
                 ],
             }
@@ -234,7 +1534,7 @@ mod tests {
             "11101110000000001101010000001100100000100011000001100000"
         );
         assert_eq!(
-            parse(&bin),
+            decode_hex("EE00D40C823060"),
             Packet::Operator {
                 version: 7,
                 type_id: 3,
@@ -243,17 +1543,17 @@ mod tests {
                     Packet::Literal {
                         version: 2,
                         type_id: 4,
-                        value: 1
+                        value: BigUint::from(1u32)
                     },
                     Packet::Literal {
                         version: 4,
                         type_id: 4,
-                        value: 2
+                        value: BigUint::from(2u32)
                     },
                     Packet::Literal {
                         version: 1,
                         type_id: 4,
-                        value: 3
+                        value: BigUint::from(3u32)
                     }
                 ],
             }
@@ -262,33 +1562,33 @@ mod tests {
 
     #[test]
     fn part_1_examples() {
-        assert_eq!(sum_versions(&parse(&hex_to_bin("8A004A801A8002F478"))), 16);
+        assert_eq!(sum_versions(&decode_hex("8A004A801A8002F478")), Ok(16));
         assert_eq!(
-            sum_versions(&parse(&hex_to_bin("620080001611562C8802118E34"))),
-            12
+            sum_versions(&decode_hex("620080001611562C8802118E34")),
+            Ok(12)
         );
         assert_eq!(
-            sum_versions(&parse(&hex_to_bin("C0015000016115A2E0802F182340"))),
-            23
+            sum_versions(&decode_hex("C0015000016115A2E0802F182340")),
+            Ok(23)
         );
         assert_eq!(
-            sum_versions(&parse(&hex_to_bin("A0016C880162017C3686B18A3D4780"))),
-            31
+            sum_versions(&decode_hex("A0016C880162017C3686B18A3D4780")),
+            Ok(31)
         );
     }
 
     #[test]
     fn part_2_examples() {
-        assert_eq!(evaluate(&parse(&hex_to_bin("C200B40A82"))), 3);
-        assert_eq!(evaluate(&parse(&hex_to_bin("04005AC33890"))), 54);
-        assert_eq!(evaluate(&parse(&hex_to_bin("880086C3E88112"))), 7);
-        assert_eq!(evaluate(&parse(&hex_to_bin("CE00C43D881120"))), 9);
-        assert_eq!(evaluate(&parse(&hex_to_bin("D8005AC2A8F0"))), 1);
-        assert_eq!(evaluate(&parse(&hex_to_bin("F600BC2D8F"))), 0);
-        assert_eq!(evaluate(&parse(&hex_to_bin("9C005AC2F8F0"))), 0);
-        assert_eq!(
-            evaluate(&parse(&hex_to_bin("9C0141080250320F1802104A08"))),
-            1
+        assert_eq!(evaluate::<usize>(&decode_hex("C200B40A82")), Ok(3));
+        assert_eq!(evaluate::<usize>(&decode_hex("04005AC33890")), Ok(54));
+        assert_eq!(evaluate::<usize>(&decode_hex("880086C3E88112")), Ok(7));
+        assert_eq!(evaluate::<usize>(&decode_hex("CE00C43D881120")), Ok(9));
+        assert_eq!(evaluate::<usize>(&decode_hex("D8005AC2A8F0")), Ok(1));
+        assert_eq!(evaluate::<usize>(&decode_hex("F600BC2D8F")), Ok(0));
+        assert_eq!(evaluate::<usize>(&decode_hex("9C005AC2F8F0")), Ok(0));
+        assert_eq!(
+            evaluate::<usize>(&decode_hex("9C0141080250320F1802104A08")),
+            Ok(1)
         );
     }
 }
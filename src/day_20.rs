@@ -1,92 +1,72 @@
+use crate::utils::grid::{Edges, Grid};
 use crate::utils::SolverResult;
-use std::collections::HashMap;
+use itertools::{iproduct, Itertools};
 use std::fs::read_to_string;
 
-type Algorithm = HashMap<usize, usize>;
-type Position = (isize, isize);
-type Image = HashMap<Position, usize>;
-
-fn window(p: &Position) -> Vec<Position> {
-    let (x, y) = *p;
-    let x_m = x - 1;
-    let x_p = x + 1;
-    let y_m = y - 1;
-    let y_p = y + 1;
-
-    vec![
-        (x_m, y_m),
-        (x, y_m),
-        (x_p, y_m),
-        (x_m, y),
-        *p,
-        (x_p, y),
-        (x_m, y_p),
-        (x, y_p),
-        (x_p, y_p),
-    ]
+type Algorithm = Vec<bool>;
+
+// The pixels we've looked at so far, and whether every pixel out past them is lit.
+#[derive(Debug, Clone)]
+struct Image {
+    pixels: Grid<bool>,
+    background: bool,
 }
 
-fn apply(algorithm: &Algorithm, image: Image, unset: usize) -> Image {
-    image
-        .keys()
-        .flat_map(window)
-        .map(|position| {
-            let bits: String = window(&position)
-                .iter()
-                .map(|p| image.get(p).unwrap_or(&unset).to_string())
-                .collect();
-
-            (
-                position,
-                *algorithm
-                    .get(&usize::from_str_radix(&bits, 2).unwrap())
-                    .unwrap(),
-            )
-        })
-        .collect()
+fn apply(algorithm: &Algorithm, image: &Image) -> Image {
+    // Every step can affect the pixels just past the edge of the image, so it grows by one on each side.
+    // Pixel (x, y) of the new image is pixel (x - 1, y - 1) of the old one.
+    let pixels = Grid::from_fn(image.pixels.width() + 2, image.pixels.height() + 2, |p| {
+        let index = iproduct!(-1..=1, -1..=1).fold(0, |index, (dy, dx)| {
+            let lit = image
+                .pixels
+                .offset(p, (dx - 1, dy - 1), Edges::Bounded)
+                .map_or(image.background, |q| image.pixels[q]);
+
+            index << 1 | lit as usize
+        });
+
+        algorithm[index]
+    });
+
+    Image {
+        pixels,
+        background: algorithm[if image.background { 511 } else { 0 }],
+    }
 }
 
-fn part_1(algorithm: &Algorithm, image: &Image) -> usize {
-    (0..2)
-        .fold(image.clone(), |image, step| {
-            apply(algorithm, image, step % 2)
-        })
+fn count_lit(algorithm: &Algorithm, image: &Image, steps: usize) -> usize {
+    (0..steps)
+        .fold(image.clone(), |image, _| apply(algorithm, &image))
+        .pixels
         .values()
-        .filter(|&v| v == &1)
+        .filter(|&&lit| lit)
         .count()
 }
 
+fn part_1(algorithm: &Algorithm, image: &Image) -> usize {
+    count_lit(algorithm, image, 2)
+}
+
 fn part_2(algorithm: &Algorithm, image: &Image) -> usize {
-    (0..50)
-        .fold(image.clone(), |image, step| {
-            apply(algorithm, image, step % 2)
-        })
-        .values()
-        .filter(|&v| v == &1)
-        .count()
+    count_lit(algorithm, image, 50)
 }
 
+// The algorithm and the image are separated by a blank line. Going line by line
+// copes with \r\n line endings too.
 fn parse_input(input: &str) -> (Algorithm, Image) {
     let mut lines = input.lines();
 
     let algorithm = lines
-        .next()
-        .unwrap()
-        .chars()
-        .map(|c| if c == '#' { 1 } else { 0 })
-        .enumerate()
+        .by_ref()
+        .take_while(|line| !line.is_empty())
+        .flat_map(|line| line.chars())
+        .map(|c| c == '#')
         .collect();
 
-    lines.next().unwrap();
-
-    let image = lines
-        .enumerate()
-        .flat_map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(move |(x, c)| ((x as isize, y as isize), if c == '#' { 1 } else { 0 }))
-        })
-        .collect();
+    let image = Image {
+        pixels: Grid::parse(&lines.join("\n"), |c| c == '#'),
+        background: false,
+    };
 
     (algorithm, image)
 }
@@ -102,9 +82,52 @@ pub fn solve() -> SolverResult {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..###..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###.######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#..#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#......#..#...##.#.##..#...##.#.##..###.#......#.#.......#.#.#.####.###.##...#.....####.#..#..#.##.#....##..#.####....##...##..#...#......#.#.......#.......##..####..#...#.#.#...##..#.#..###..#####........#..####......#..#
+
+#..#.
+#....
+##..#
+..#..
+..###";
+
     #[test]
-    fn part_1_examples() {}
+    fn part_1_examples() {
+        let (algorithm, image) = parse_input(INPUT);
+
+        let enhanced = apply(&algorithm, &image);
+        assert_eq!(
+            enhanced
+                .pixels
+                .map(|&lit| if lit { '#' } else { '.' })
+                .to_string(),
+            "\
+.##.##.
+#..#.#.
+##.#..#
+####..#
+.#..##.
+..##..#
+...#.#."
+        );
+
+        assert_eq!(part_1(&algorithm, &image), 35);
+    }
+
+    #[test]
+    fn line_endings() {
+        let (algorithm, image) = parse_input(&INPUT.replace('\n', "\r\n"));
+
+        assert_eq!(algorithm.len(), 512);
+        assert_eq!((image.pixels.width(), image.pixels.height()), (5, 5));
+        assert_eq!(part_1(&algorithm, &image), 35);
+    }
 
     #[test]
-    fn part_2_examples() {}
+    fn part_2_examples() {
+        let (algorithm, image) = parse_input(INPUT);
+        assert_eq!(part_2(&algorithm, &image), 3351);
+    }
 }
@@ -1,9 +1,13 @@
 use crate::utils::SolverResult;
 use itertools::Itertools;
-use regex::Regex;
-use std::fs::read_to_string;
+use std::error::Error;
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::iter;
+use std::ops::Range;
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 struct Dim {
     lower: isize,
     upper: isize,
@@ -15,7 +19,7 @@ impl Dim {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 struct Cuboid {
     dims: Vec<Dim>,
     sign: isize,
@@ -43,7 +47,7 @@ impl Cuboid {
                 })
                 .collect(),
             // This is the sign-alternation in the inclusion-exclusion rule;
-            // unfortunately it depends on the choice of ordering in the fold in count_on
+            // unfortunately it depends on the choice of ordering in Reactor::apply
             // (which cuboid we use as the argument:
             // it should be the existing cuboid, which represents an existing intersection term).
             sign: -other.sign,
@@ -51,85 +55,669 @@ impl Cuboid {
 
         (c.dims.iter().all(|d| d.is_valid())).then_some(c)
     }
+
+    fn contains(&self, point: &[isize]) -> bool {
+        self.dims
+            .iter()
+            .zip(point)
+            .all(|(d, p)| (d.lower..=d.upper).contains(p))
+    }
+
+    // Splits off the parts of this cuboid that aren't covered by other,
+    // one slab on either side per axis.
+    fn subtract(&self, other: &Self) -> Vec<Self> {
+        let overlap = match self.intersection(other) {
+            Some(overlap) => overlap,
+            None => return vec![self.clone()],
+        };
+
+        let mut rest = self.clone();
+        let mut pieces = vec![];
+
+        overlap.dims.iter().enumerate().for_each(|(axis, o)| {
+            let r = rest.dims[axis];
+
+            if r.lower < o.lower {
+                let mut piece = rest.clone();
+                piece.dims[axis].upper = o.lower - 1;
+                pieces.push(piece);
+            }
+            if o.upper < r.upper {
+                let mut piece = rest.clone();
+                piece.dims[axis].lower = o.upper + 1;
+                pieces.push(piece);
+            }
+
+            rest.dims[axis] = *o;
+        });
+
+        pieces
+    }
 }
 
-fn count_on(steps: &[Cuboid], initialization: bool) -> isize {
-    steps
-        .iter()
-        .filter(|step| !initialization || step.dims.iter().all(|d| d.upper <= 50 && d.lower >= -50))
-        .fold(Vec::new(), |mut cuboids: Vec<Cuboid>, step| {
-            // We're going to do inclusion-exclusion here, but iteratively, adding
-            // one new cuboid (i.e., set of points) at a time.
-            // This is significantly faster than trying to iterate over the powerset,
-            // because we can pre-emptively eliminate intersections that lead to empty sets
-            // early in the calculation.
-            // If we just started with the powerset, the vast, vast majority of those
-            // intersections would be empty, but we wouldn't know it until we started
-            // digging through the term.
-
-            let mut intersections = cuboids
+// Axis names for display; anything past these is numbered instead.
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+
+fn axis_name(axis: usize) -> String {
+    AXES.get(axis)
+        .map_or_else(|| format!("a{}", axis), |name| name.to_string())
+}
+
+impl fmt::Display for Cuboid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            if self.sign == 1 { "on" } else { "off" },
+            self.dims
                 .iter()
-                .filter_map(|cuboid| step.intersection(cuboid))
-                .collect_vec();
+                .enumerate()
+                .map(|(axis, d)| format!("{}={}..{}", axis_name(axis), d.lower, d.upper))
+                .join(",")
+        )
+    }
+}
 
-            cuboids.append(&mut intersections);
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum StepError {
+    // A line that isn't a step at all
+    Malformed {
+        line: usize,
+        text: String,
+    },
+    // An axis that is out of order, e.g. "y" where "x" should be
+    Axis {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+    // A step with a different number of axes than the reactor
+    Dimensions {
+        expected: usize,
+        found: usize,
+    },
+}
 
-            // The only change to the inclusion-exclusion rule is that we don't add the
-            // initial (single-set) terms for "off" cuboids.
-            // This propagates down the chain to mean that we don't have any
-            // intersection terms that "start" with an "off" cuboid.
-            if step.sign == 1 {
-                cuboids.push(step.clone());
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepError::Malformed { line, text } => {
+                write!(f, "line {} is not a step: {:?}", line, text)
             }
+            StepError::Axis {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected axis {} but found {} on line {}",
+                expected, found, line
+            ),
+            StepError::Dimensions { expected, found } => write!(
+                f,
+                "expected a step with {} axes but found {}",
+                expected, found
+            ),
+        }
+    }
+}
 
-            cuboids
+impl Error for StepError {}
+
+// The state of the reactor, kept as the list of inclusion-exclusion terms
+// built up by the steps applied so far.
+#[derive(Debug, Clone)]
+struct Reactor {
+    dimensions: usize,
+    cuboids: Vec<Cuboid>,
+}
+
+impl Reactor {
+    fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            cuboids: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, step: &Cuboid) -> Result<(), StepError> {
+        check_dimensions(step, self.dimensions)?;
+
+        // We're going to do inclusion-exclusion here, but iteratively, adding
+        // one new cuboid (i.e., set of points) at a time.
+        // This is significantly faster than trying to iterate over the powerset,
+        // because we can pre-emptively eliminate intersections that lead to empty sets
+        // early in the calculation.
+        // If we just started with the powerset, the vast, vast majority of those
+        // intersections would be empty, but we wouldn't know it until we started
+        // digging through the term.
+
+        let mut intersections = self
+            .cuboids
+            .iter()
+            .filter_map(|cuboid| step.intersection(cuboid))
+            .collect_vec();
+
+        self.cuboids.append(&mut intersections);
+
+        // The only change to the inclusion-exclusion rule is that we don't add the
+        // initial (single-set) terms for "off" cuboids.
+        // This propagates down the chain to mean that we don't have any
+        // intersection terms that "start" with an "off" cuboid.
+        if step.sign == 1 {
+            self.cuboids.push(step.clone());
+        }
+
+        Ok(())
+    }
+
+    fn is_on(&self, point: &[isize]) -> bool {
+        // Every term covering the point contributes its sign, and those add up
+        // to exactly 1 or 0.
+        self.cuboids
+            .iter()
+            .filter(|cuboid| cuboid.contains(point))
+            .map(|cuboid| cuboid.sign)
+            .sum::<isize>()
+            == 1
+    }
+
+    fn count_on_in(&self, region: &[Dim]) -> isize {
+        let region = Cuboid {
+            dims: region.to_vec(),
+            sign: 1,
+        };
+
+        // Clipping each term to the region gives the inclusion-exclusion terms for the
+        // region alone; intersection flips the sign of the existing term, so flip it back.
+        self.cuboids
+            .iter()
+            .filter_map(|cuboid| region.intersection(cuboid))
+            .map(|cuboid| -cuboid.volume())
+            .sum()
+    }
+
+    fn count_on(&self) -> isize {
+        self.cuboids.iter().map(|cuboid| cuboid.volume()).sum()
+    }
+}
+
+// The reactor state as a set of non-overlapping "on" cuboids, which describes the
+// final shape directly instead of as a sum of signed terms.
+#[derive(Debug, Clone)]
+struct DisjointReactor {
+    dimensions: usize,
+    cuboids: Vec<Cuboid>,
+}
+
+impl DisjointReactor {
+    fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            cuboids: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, step: &Cuboid) -> Result<(), StepError> {
+        check_dimensions(step, self.dimensions)?;
+
+        // Carve the step out of everything it overlaps, then (if it turns cells on)
+        // add it back as a single cuboid.
+        self.cuboids = self
+            .cuboids
+            .iter()
+            .flat_map(|cuboid| cuboid.subtract(step))
+            .collect();
+
+        if step.sign == 1 {
+            self.cuboids.push(Cuboid {
+                dims: step.dims.clone(),
+                sign: 1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn count_on(&self) -> isize {
+        self.cuboids.iter().map(|cuboid| cuboid.volume()).sum()
+    }
+
+    // The on-region with the split cuboids glued back together, or the region's canonical
+    // form (see canonical) glued together if that has no more cuboids. Finding the fewest
+    // cuboids is NP-hard in three or more dimensions, so neither is guaranteed to be minimal,
+    // but the result is never more than the split cuboids glued together.
+    fn merged(&self) -> Vec<Cuboid> {
+        let boxes = self.cuboids.iter().map(|c| c.dims.as_slice()).collect_vec();
+        let glued = coalesce(boxes.iter().map(|b| b.to_vec()).collect());
+
+        // The first of equal lengths wins, so the canonical form is kept when it can be
+        [coalesce(canonical(&boxes)), glued]
+            .into_iter()
+            .min_by_key(|cuboids| cuboids.len())
+            .unwrap()
+            .into_iter()
+            .sorted()
+            .map(|dims| Cuboid { dims, sign: 1 })
+            .collect()
+    }
+}
+
+// Glues boxes that line up exactly along one axis together, a sweep per axis until
+// nothing changes.
+fn coalesce(mut boxes: Vec<Vec<Dim>>) -> Vec<Vec<Dim>> {
+    let dimensions = boxes.first().map_or(0, |b| b.len());
+
+    loop {
+        let before = boxes.len();
+
+        (0..dimensions).for_each(|axis| {
+            // Sorting by every other axis first puts boxes that could be merged
+            // along this axis next to each other.
+            boxes.sort_by_key(|dims| {
+                let mut key = dims.clone();
+                key.remove(axis);
+                (key, dims[axis])
+            });
+
+            boxes = boxes
+                .drain(..)
+                .coalesce(|mut a, b| {
+                    let lines_up =
+                        (0..dimensions).all(|other| other == axis || a[other] == b[other]);
+
+                    if lines_up && a[axis].upper + 1 == b[axis].lower {
+                        a[axis].upper = b[axis].upper;
+                        Ok(a)
+                    } else {
+                        Err((a, b))
+                    }
+                })
+                .collect();
+        });
+
+        if boxes.len() == before {
+            return boxes;
+        }
+    }
+}
+
+// The union of some disjoint boxes in canonical form: cut along the first axis wherever the
+// cross-section changes, then cut each cross-section the same way along the remaining axes,
+// gluing equal neighbouring slabs together. The result depends only on the points in the
+// union, not on how it was split into boxes.
+fn canonical(boxes: &[&[Dim]]) -> Vec<Vec<Dim>> {
+    match boxes.first() {
+        None => return vec![],
+        Some([]) => return vec![vec![]],
+        _ => {}
+    }
+
+    let boundaries = boxes
+        .iter()
+        .flat_map(|b| [b[0].lower, b[0].upper + 1])
+        .sorted()
+        .dedup()
+        .collect_vec();
+    let mut by_lower = boxes.iter().sorted_by_key(|b| b[0].lower).peekable();
+    let mut active: Vec<&[Dim]> = vec![];
+
+    // Slabs between consecutive boundaries, with the same cross-section merged
+    let mut slabs: Vec<(Dim, Vec<Vec<Dim>>)> = vec![];
+    boundaries
+        .iter()
+        .tuple_windows()
+        .for_each(|(&lower, &next)| {
+            active.retain(|b| b[0].upper >= lower);
+            while let Some(b) = by_lower.next_if(|b| b[0].lower <= lower) {
+                active.push(b);
+            }
+
+            let section = canonical(&active.iter().map(|b| &b[1..]).collect_vec());
+            match slabs.last_mut() {
+                _ if section.is_empty() => {}
+                Some((dim, last)) if dim.upper + 1 == lower && *last == section => {
+                    dim.upper = next - 1
+                }
+                _ => slabs.push((
+                    Dim {
+                        lower,
+                        upper: next - 1,
+                    },
+                    section,
+                )),
+            }
+        });
+
+    slabs
+        .into_iter()
+        .flat_map(|(dim, section)| {
+            section
+                .into_iter()
+                .map(move |rest| iter::once(dim).chain(rest).collect())
         })
+        .collect()
+}
+
+fn to_text(cuboids: &[Cuboid]) -> String {
+    cuboids.iter().map(|cuboid| cuboid.to_string()).join("\n")
+}
+
+fn to_json(cuboids: &[Cuboid]) -> String {
+    format!(
+        "[\n{}\n]",
+        cuboids
+            .iter()
+            .map(|cuboid| {
+                format!(
+                    "  {{{}}}",
+                    cuboid
+                        .dims
+                        .iter()
+                        .enumerate()
+                        .map(|(axis, d)| format!(
+                            "\"{}\": [{}, {}]",
+                            axis_name(axis),
+                            d.lower,
+                            d.upper
+                        ))
+                        .join(", ")
+                )
+            })
+            .join(",\n")
+    )
+}
+
+fn check_dimensions(step: &Cuboid, dimensions: usize) -> Result<(), StepError> {
+    if step.dims.len() == dimensions {
+        Ok(())
+    } else {
+        Err(StepError::Dimensions {
+            expected: dimensions,
+            found: step.dims.len(),
+        })
+    }
+}
+
+// The number of axes the steps have, or the puzzle's three if there aren't any steps.
+fn dimensions(steps: &[Cuboid]) -> usize {
+    steps.first().map_or(3, |step| step.dims.len())
+}
+
+fn is_initialization(step: &Cuboid) -> bool {
+    step.dims.iter().all(|d| d.upper <= 50 && d.lower >= -50)
+}
+
+fn count_on(steps: &[Cuboid], initialization: bool) -> Result<isize, StepError> {
+    Ok(steps
+        .iter()
+        .filter(|step| !initialization || is_initialization(step))
+        .try_fold(Reactor::new(dimensions(steps)), |mut reactor, step| {
+            reactor.apply(step)?;
+            Ok(reactor)
+        })?
+        .count_on())
+}
+
+fn count_on_disjoint(steps: &[Cuboid], initialization: bool) -> Result<isize, StepError> {
+    Ok(steps
+        .iter()
+        .filter(|step| !initialization || is_initialization(step))
+        .try_fold(
+            DisjointReactor::new(dimensions(steps)),
+            |mut reactor, step| {
+                reactor.apply(step)?;
+                Ok(reactor)
+            },
+        )?
+        .count_on())
+}
+
+// All combinations of indices from the given ranges, including the single empty
+// combination when there are no ranges at all.
+fn combinations(ranges: &[Range<usize>]) -> Box<dyn Iterator<Item = Vec<usize>> + '_> {
+    if ranges.is_empty() {
+        Box::new(iter::once(vec![]))
+    } else {
+        Box::new(ranges.iter().cloned().multi_cartesian_product())
+    }
+}
+
+fn set_bits(bits: &mut [u64], range: Range<usize>, value: bool) {
+    let mut i = range.start;
+
+    while i < range.end {
+        let offset = i % 64;
+        let length = (64 - offset).min(range.end - i);
+        let mask = (u64::MAX >> (64 - length)) << offset;
+
+        if value {
+            bits[i / 64] |= mask;
+        } else {
+            bits[i / 64] &= !mask;
+        }
+
+        i += length;
+    }
+}
+
+// Compresses each axis down to the distinct step boundaries along it, so every cell
+// of the compressed grid is either entirely on or entirely off, then replays the
+// steps onto a bitset of those cells.
+fn count_on_compressed(steps: &[Cuboid], initialization: bool) -> Result<isize, StepError> {
+    let dimensions = dimensions(steps);
+    let steps = steps
+        .iter()
+        .filter(|step| !initialization || is_initialization(step))
+        .collect_vec();
+
+    steps
         .iter()
-        .map(|cuboid| cuboid.volume())
-        .sum()
+        .try_for_each(|step| check_dimensions(step, dimensions))?;
+    if steps.is_empty() {
+        return Ok(0);
+    }
+
+    // Compressed cell i along an axis covers boundaries[i]..boundaries[i + 1]
+    let boundaries = (0..dimensions)
+        .map(|axis| {
+            steps
+                .iter()
+                .flat_map(|step| [step.dims[axis].lower, step.dims[axis].upper + 1])
+                .sorted()
+                .dedup()
+                .collect_vec()
+        })
+        .collect_vec();
+    let widths = boundaries
+        .iter()
+        .map(|b| b.array_windows().map(|[l, u]| u - l).collect_vec())
+        .collect_vec();
+
+    // Cells are laid out row-major, so the last axis is contiguous in the bitset.
+    let shape = widths.iter().map(|w| w.len()).collect_vec();
+    let mut strides = shape
+        .iter()
+        .rev()
+        .scan(1, |stride, &length| {
+            let s = *stride;
+            *stride *= length;
+            Some(s)
+        })
+        .collect_vec();
+    strides.reverse();
+
+    let mut on = vec![0u64; shape.iter().product::<usize>().div_ceil(64)];
+    let start =
+        |prefix: &[usize]| -> usize { prefix.iter().zip(&strides).map(|(i, s)| i * s).sum() };
+
+    steps.iter().for_each(|step| {
+        let ranges = step
+            .dims
+            .iter()
+            .zip(&boundaries)
+            .map(|(d, b)| {
+                b.binary_search(&d.lower).unwrap()..b.binary_search(&(d.upper + 1)).unwrap()
+            })
+            .collect_vec();
+        let (last, rest) = ranges.split_last().unwrap();
+
+        combinations(rest).for_each(|prefix| {
+            let s = start(&prefix);
+            set_bits(&mut on, s + last.start..s + last.end, step.sign == 1);
+        });
+    });
+
+    let cells = shape.iter().map(|&length| 0..length).collect_vec();
+    let (last, rest) = cells.split_last().unwrap();
+    let last_widths = widths.last().unwrap();
+
+    Ok(combinations(rest)
+        .map(|prefix| {
+            let s = start(&prefix);
+            let area: isize = prefix.iter().zip(&widths).map(|(&i, w)| w[i]).product();
+
+            area * last
+                .clone()
+                .filter(|i| on[(s + i) / 64] & (1 << ((s + i) % 64)) != 0)
+                .map(|i| last_widths[i])
+                .sum::<isize>()
+        })
+        .sum())
+}
+
+// The ways of counting cells that are on, which can be picked from the command line.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Method {
+    #[default]
+    InclusionExclusion,
+    Disjoint,
+    Compressed,
 }
 
-lazy_static! {
-    static ref RE: Regex =
-        Regex::new(r"(on|off) x=(-?\d+..-?\d+),y=(-?\d+..-?\d+),z=(-?\d+..-?\d+)").unwrap();
+impl Method {
+    pub const NAMES: [&'static str; 3] = ["inclusion-exclusion", "disjoint", "compressed"];
+
+    fn count_on(&self, steps: &[Cuboid], initialization: bool) -> Result<isize, StepError> {
+        match self {
+            Method::InclusionExclusion => count_on(steps, initialization),
+            Method::Disjoint => count_on_disjoint(steps, initialization),
+            Method::Compressed => count_on_compressed(steps, initialization),
+        }
+    }
 }
 
-fn parse_range(range: &str) -> Dim {
-    let (lower, upper) = range.split_once("..").unwrap();
+impl FromStr for Method {
+    type Err = String;
 
-    Dim {
-        lower: lower.parse().unwrap(),
-        upper: upper.parse().unwrap(),
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inclusion-exclusion" => Ok(Method::InclusionExclusion),
+            "disjoint" => Ok(Method::Disjoint),
+            "compressed" => Ok(Method::Compressed),
+            _ => Err(format!(
+                "unknown method {:?}, expected one of {}",
+                s,
+                Method::NAMES.join(", ")
+            )),
+        }
     }
 }
 
-fn parse_input(input: &str) -> Vec<Cuboid> {
+fn parse_range(range: &str) -> Option<Dim> {
+    let (lower, upper) = range.split_once("..")?;
+
+    Some(Dim {
+        lower: lower.parse().ok()?,
+        upper: upper.parse().ok()?,
+    })
+}
+
+// Steps can have any number of axes, e.g. "on x=1..2,y=3..4,z=5..6,w=7..8",
+// but they must be listed in order (see axis_name). Lines are numbered from 1.
+fn parse_step(number: usize, line: &str) -> Result<Cuboid, StepError> {
+    let malformed = || StepError::Malformed {
+        line: number,
+        text: line.to_string(),
+    };
+
+    let (state, ranges) = line.split_once(' ').ok_or_else(malformed)?;
+    let sign = match state {
+        "on" => 1,
+        "off" => -1,
+        _ => return Err(malformed()),
+    };
+
+    Ok(Cuboid {
+        dims: ranges
+            .split(',')
+            .enumerate()
+            .map(|(axis, range)| {
+                let (name, range) = range.split_once('=').ok_or_else(malformed)?;
+                if name != axis_name(axis) {
+                    return Err(StepError::Axis {
+                        line: number,
+                        expected: axis_name(axis),
+                        found: name.to_string(),
+                    });
+                }
+                parse_range(range).ok_or_else(malformed)
+            })
+            .try_collect()?,
+        sign,
+    })
+}
+
+fn parse_input(input: &str) -> Result<Vec<Cuboid>, StepError> {
     input
         .lines()
-        .map(|line| {
-            let captures = RE.captures(line).unwrap();
-
-            let x = parse_range(&captures[2]);
-            let y = parse_range(&captures[3]);
-            let z = parse_range(&captures[4]);
-
-            Cuboid {
-                dims: vec![x, y, z],
-                sign: match &captures[1] {
-                    "on" => 1,
-                    "off" => -1,
-                    _ => unreachable!(),
-                },
-            }
-        })
+        .enumerate()
+        .map(|(i, line)| parse_step(i + 1, line))
         .collect()
 }
 
 pub fn solve() -> SolverResult {
-    let steps = parse_input(&read_to_string("data/day_22.txt")?);
+    solve_with(Method::default(), false)
+}
+
+// Optionally also queries the final reactor and writes the cubes left on to
+// data/day_22_on.txt and data/day_22_on.json.
+pub fn solve_with(method: Method, export: bool) -> SolverResult {
+    let steps = parse_input(&read_to_string("data/day_22.txt")?)?;
+
+    println!("Part 1: {}", method.count_on(&steps, true)?);
+    println!("Part 2: {}", method.count_on(&steps, false)?);
+
+    if !export {
+        return Ok(());
+    }
 
-    println!("Part 1: {}", count_on(&steps, true));
-    println!("Part 2: {}", count_on(&steps, false));
+    let mut reactor = Reactor::new(dimensions(&steps));
+    steps.iter().try_for_each(|step| reactor.apply(step))?;
+
+    let origin = vec![0; reactor.dimensions];
+    let initialization = Dim {
+        lower: -50,
+        upper: 50,
+    };
+    let region = vec![initialization; reactor.dimensions];
+    println!("Origin is on: {}", reactor.is_on(&origin));
+    println!(
+        "On within 50 of the origin: {}",
+        reactor.count_on_in(&region)
+    );
+
+    let mut disjoint = DisjointReactor::new(reactor.dimensions);
+    steps.iter().try_for_each(|step| disjoint.apply(step))?;
+    let cuboids = disjoint.merged();
+
+    write("data/day_22_on.txt", to_text(&cuboids))?;
+    write("data/day_22_on.json", to_json(&cuboids))?;
+    println!(
+        "Wrote {} disjoint cuboids ({} on) to data/day_22_on.txt and data/day_22_on.json",
+        cuboids.len(),
+        disjoint.count_on()
+    );
 
     Ok(())
 }
@@ -137,6 +725,9 @@ pub fn solve() -> SolverResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::random::xorshift;
+    use std::collections::HashSet;
+    use std::time::Instant;
 
     const SMALL_INPUT: &str = "\
 on x=10..12,y=10..12,z=10..12
@@ -230,18 +821,377 @@ off x=-70369..-16548,y=22648..78696,z=-1892..86821
 on x=-53470..21291,y=-120233..-33476,z=-44150..38147
 off x=-93533..-4276,y=-16170..68771,z=-104985..-24507";
 
+    const FOUR_DIMENSIONAL_INPUT: &str = "\
+on x=0..4,y=0..4,z=0..4,w=0..4
+on x=2..6,y=-1..3,z=1..5,w=2..3
+off x=1..3,y=1..3,z=1..3,w=1..3
+on x=3..3,y=2..2,z=2..2,w=0..6
+off x=-2..1,y=0..6,z=4..9,w=-1..2";
+
+    fn parse(input: &str) -> Vec<Cuboid> {
+        parse_input(input).unwrap()
+    }
+
+    fn parse_step_ok(line: &str) -> Cuboid {
+        parse_step(1, line).unwrap()
+    }
+
+    // Applies steps one cell at a time, to check the reactor against.
+    fn brute_force(steps: &[Cuboid]) -> HashSet<Vec<isize>> {
+        steps.iter().fold(HashSet::new(), |mut on, step| {
+            step.dims
+                .iter()
+                .map(|d| d.lower..=d.upper)
+                .multi_cartesian_product()
+                .for_each(|point| {
+                    if step.sign == 1 {
+                        on.insert(point);
+                    } else {
+                        on.remove(&point);
+                    }
+                });
+            on
+        })
+    }
+
     #[test]
-    fn part_1_examples() {
-        assert_eq!(count_on(&parse_input(SMALL_INPUT), true), 39);
-        assert_eq!(count_on(&parse_input(LARGE_INPUT), true), 590784);
+    fn point_queries() {
+        let mut reactor = Reactor::new(3);
+        let steps = parse(SMALL_INPUT);
+
+        reactor.apply(&steps[0]).unwrap();
+        assert!(reactor.is_on(&[10, 10, 10]));
+        assert!(!reactor.is_on(&[13, 13, 13]));
+
+        reactor.apply(&steps[1]).unwrap();
+        assert!(reactor.is_on(&[13, 13, 13]));
+
+        reactor.apply(&steps[2]).unwrap();
+        assert!(!reactor.is_on(&[10, 10, 10]));
+        assert!(reactor.is_on(&[12, 12, 12]));
+
+        reactor.apply(&steps[3]).unwrap();
+        assert!(reactor.is_on(&[10, 10, 10]));
+        assert!(!reactor.is_on(&[9, 9, 9]));
+        assert_eq!(reactor.count_on(), 39);
     }
 
     #[test]
-    fn part_2_examples() {
-        assert_eq!(count_on(&parse_input(LARGER_INPUT), true), 474140);
+    fn region_queries() {
+        let steps = parse(FOUR_DIMENSIONAL_INPUT);
+        assert!(steps.iter().all(|step| step.dims.len() == 4));
+
+        let mut reactor = Reactor::new(4);
+        steps.iter().for_each(|step| reactor.apply(step).unwrap());
+
+        let on = brute_force(&steps);
+        assert_eq!(reactor.count_on(), on.len() as isize);
+
+        let region = parse_step_ok("on x=1..4,y=0..2,z=2..5,w=-3..3").dims;
+        let region_cuboid = Cuboid {
+            dims: region.clone(),
+            sign: 1,
+        };
+        assert_eq!(
+            reactor.count_on_in(&region),
+            on.iter().filter(|p| region_cuboid.contains(p)).count() as isize
+        );
+
+        (-3..8)
+            .map(|_| -2..8)
+            .take(4)
+            .multi_cartesian_product()
+            .for_each(|point| assert_eq!(reactor.is_on(&point), on.contains(&point)));
+    }
+
+    fn disjoint(steps: &[Cuboid]) -> DisjointReactor {
+        let mut reactor = DisjointReactor::new(dimensions(steps));
+        steps.iter().for_each(|step| reactor.apply(step).unwrap());
+        reactor
+    }
+
+    fn assert_disjoint(cuboids: &[Cuboid]) {
+        cuboids
+            .iter()
+            .tuple_combinations()
+            .for_each(|(a, b)| assert!(a.intersection(b).is_none(), "{} overlaps {}", a, b));
+    }
+
+    #[test]
+    fn disjoint_examples() {
+        [
+            SMALL_INPUT,
+            LARGE_INPUT,
+            LARGER_INPUT,
+            FOUR_DIMENSIONAL_INPUT,
+        ]
+        .iter()
+        .for_each(|input| {
+            let steps = parse(input);
+            let reactor = disjoint(&steps);
+            let merged = reactor.merged();
+
+            assert_eq!(Ok(reactor.count_on()), count_on(&steps, false));
+            assert_eq!(
+                merged.iter().map(|c| c.volume()).sum::<isize>(),
+                reactor.count_on()
+            );
+            assert!(merged.len() <= reactor.cuboids.len());
+            assert_disjoint(&merged);
+
+            // Rebuilding the region from its own cuboids, in any order, gives them back
+            let mut reversed = merged.clone();
+            reversed.reverse();
+            assert_eq!(disjoint(&reversed).merged(), merged);
+
+            // Exported text is a valid list of steps that reproduces the region
+            assert_eq!(
+                count_on(&parse(&to_text(&merged)), false),
+                Ok(reactor.count_on())
+            );
+        });
+
         assert_eq!(
-            count_on(&parse_input(LARGER_INPUT), false),
-            2758514936282235
+            brute_force(&parse(FOUR_DIMENSIONAL_INPUT)).len() as isize,
+            disjoint(&parse(FOUR_DIMENSIONAL_INPUT)).count_on()
         );
     }
+
+    // Steps with ranges like the real input's, which split into more awkward shapes than the examples.
+    fn random_steps(seed: u64, count: usize) -> Vec<Cuboid> {
+        let mut random = xorshift(seed).map(|r| r as isize & isize::MAX);
+
+        (0..count)
+            .map(|_| {
+                let dims = (0..3)
+                    .map(|_| {
+                        let lower = random.next().unwrap() % 100_000 - 50_000;
+                        let size = random.next().unwrap() % 40_000;
+                        Dim {
+                            lower,
+                            upper: lower + size,
+                        }
+                    })
+                    .collect();
+                let sign = if random.next().unwrap() % 3 == 0 {
+                    -1
+                } else {
+                    1
+                };
+
+                Cuboid { dims, sign }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merged_size() {
+        (1..=5).for_each(|seed| {
+            let steps = random_steps(seed, 100);
+            let reactor = disjoint(&steps);
+            let merged = reactor.merged();
+
+            let glued = coalesce(reactor.cuboids.iter().map(|c| c.dims.clone()).collect());
+            assert!(merged.len() <= glued.len());
+            assert!(merged.len() <= reactor.cuboids.len());
+            assert_disjoint(&merged);
+            assert_eq!(
+                merged.iter().map(|c| c.volume()).sum::<isize>(),
+                reactor.count_on()
+            );
+            assert_eq!(Ok(reactor.count_on()), count_on(&steps, false));
+        });
+    }
+
+    #[test]
+    fn methods_agree() {
+        [
+            SMALL_INPUT,
+            LARGE_INPUT,
+            LARGER_INPUT,
+            FOUR_DIMENSIONAL_INPUT,
+        ]
+        .iter()
+        .for_each(|input| {
+            let steps = parse(input);
+
+            [true, false].iter().for_each(|&initialization| {
+                let expected = count_on(&steps, initialization);
+
+                assert_eq!(count_on_compressed(&steps, initialization), expected);
+                assert_eq!(count_on_disjoint(&steps, initialization), expected);
+            });
+        });
+
+        assert_eq!(
+            count_on_compressed(&parse("on x=3..7\noff x=5..5\non x=-2..-1"), false),
+            Ok(6)
+        );
+        assert_eq!("compressed".parse(), Ok(Method::Compressed));
+        assert!("quadtree".parse::<Method>().is_err());
+    }
+
+    #[test]
+    fn step_errors() {
+        Method::NAMES.iter().for_each(|name| {
+            let method: Method = name.parse().unwrap();
+            assert_eq!(method.count_on(&[], false), Ok(0));
+        });
+
+        assert_eq!(
+            parse_input("on x=0..1,y=0..1,z=0..1\non x=0..1,z=0..1,y=0..1").map(|_| ()),
+            Err(StepError::Axis {
+                line: 2,
+                expected: "y".to_string(),
+                found: "z".to_string()
+            })
+        );
+        assert_eq!(
+            parse_input("on x=0..1,y=0..1,z=0..1,w=0..1,a4=0..1").map(|steps| steps[0].dims.len()),
+            Ok(5)
+        );
+
+        let malformed = |line: usize, text: &str| {
+            Err(StepError::Malformed {
+                line,
+                text: text.to_string(),
+            })
+        };
+        [
+            "",
+            "toggle x=0..1,y=0..1,z=0..1",
+            "on",
+            "on x=0..1,y,z=0..1",
+            "on x=0..1,y=0-1,z=0..1",
+            "on x=0..1,y=0..one,z=0..1",
+        ]
+        .iter()
+        .for_each(|&text| {
+            assert_eq!(
+                parse_input(&format!(
+                    "on x=0..1,y=0..1,z=0..1\n{}\noff x=0..0,y=0..0,z=0..0",
+                    text
+                ))
+                .map(|_| ()),
+                malformed(2, text)
+            );
+        });
+        assert_eq!(
+            StepError::Malformed {
+                line: 3,
+                text: "toggle".to_string()
+            }
+            .to_string(),
+            "line 3 is not a step: \"toggle\""
+        );
+
+        let steps = parse("on x=0..1,y=0..1,z=0..1\non x=0..1,y=0..1");
+        let mismatch = Err(StepError::Dimensions {
+            expected: 3,
+            found: 2,
+        });
+        assert_eq!(Reactor::new(3).apply(&steps[1]), mismatch);
+        assert_eq!(DisjointReactor::new(3).apply(&steps[1]), mismatch);
+        Method::NAMES.iter().for_each(|name| {
+            let method: Method = name.parse().unwrap();
+            assert_eq!(method.count_on(&steps, false), mismatch.clone().map(|()| 0));
+        });
+    }
+
+    #[test]
+    fn export_formats() {
+        let cuboids = disjoint(&parse(
+            "\
+on x=0..1,y=0..1,z=0..1
+on x=2..3,y=0..1,z=0..1
+off x=1..2,y=0..1,z=1..1",
+        ))
+        .merged();
+
+        // The same region built up differently
+        assert_eq!(
+            disjoint(&parse(
+                "\
+on x=0..3,y=1..1,z=0..0
+on x=0..0,y=0..1,z=1..1
+on x=0..3,y=0..0,z=0..0
+on x=3..3,y=0..1,z=1..1",
+            ))
+            .merged(),
+            cuboids
+        );
+
+        assert_eq!(
+            to_text(&cuboids),
+            "\
+on x=0..0,y=0..1,z=0..1
+on x=1..2,y=0..1,z=0..0
+on x=3..3,y=0..1,z=0..1"
+        );
+        assert_eq!(
+            to_json(&cuboids),
+            r#"[
+  {"x": [0, 0], "y": [0, 1], "z": [0, 1]},
+  {"x": [1, 2], "y": [0, 1], "z": [0, 0]},
+  {"x": [3, 3], "y": [0, 1], "z": [0, 1]}
+]"#
+        );
+    }
+
+    // Run with cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_disjoint() {
+        let mut inputs = vec![("LARGER_INPUT", LARGER_INPUT.to_string())];
+        if let Ok(input) = read_to_string("data/day_22.txt") {
+            inputs.push(("data/day_22.txt", input));
+        }
+
+        inputs.iter().for_each(|(name, input)| {
+            let steps = parse(input);
+
+            let before = Instant::now();
+            let mut reactor = Reactor::new(3);
+            steps.iter().for_each(|step| reactor.apply(step).unwrap());
+            let count = reactor.count_on();
+            let inclusion_exclusion = before.elapsed();
+
+            let before = Instant::now();
+            let disjoint = disjoint(&steps);
+            assert_eq!(disjoint.count_on(), count);
+            let splitting = before.elapsed();
+
+            let before = Instant::now();
+            let merged = disjoint.merged();
+            let merging = before.elapsed();
+
+            let before = Instant::now();
+            assert_eq!(count_on_compressed(&steps, false), Ok(count));
+            let compressed = before.elapsed();
+
+            println!(
+                "{}: inclusion-exclusion {} terms in {:.2?}, disjoint {} cuboids in {:.2?}, merged to {} in {:.2?}, compressed in {:.2?}",
+                name,
+                reactor.cuboids.len(),
+                inclusion_exclusion,
+                disjoint.cuboids.len(),
+                splitting,
+                merged.len(),
+                merging,
+                compressed
+            );
+        });
+    }
+
+    #[test]
+    fn part_1_examples() {
+        assert_eq!(count_on(&parse(SMALL_INPUT), true), Ok(39));
+        assert_eq!(count_on(&parse(LARGE_INPUT), true), Ok(590784));
+    }
+
+    #[test]
+    fn part_2_examples() {
+        assert_eq!(count_on(&parse(LARGER_INPUT), true), Ok(474140));
+        assert_eq!(count_on(&parse(LARGER_INPUT), false), Ok(2758514936282235));
+    }
 }
@@ -1,25 +1,287 @@
+use std::fmt;
+use std::fs::read_to_string;
+
+use itertools::Itertools;
+
+use crate::utils::graph::{dijkstra, Graph};
 use crate::utils::SolverResult;
 
-fn part_1() -> usize {
-    0
+const EMPTY: char = '.';
+const HALLWAY_LENGTH: usize = 11;
+const ENTRANCES: [usize; 4] = [2, 4, 6, 8];
+
+// The two lines that get unfolded into the diagram for part 2.
+const UNFOLDED: [[char; 4]; 2] = [['D', 'C', 'B', 'A'], ['D', 'B', 'A', 'C']];
+
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug)]
+struct Burrow {
+    hallway: [char; HALLWAY_LENGTH],
+    // Each room is listed from the hallway down.
+    rooms: [Vec<char>; 4],
 }
 
-fn part_2() -> usize {
-    0
+fn energy(amphipod: char) -> usize {
+    match amphipod {
+        'A' => 1,
+        'B' => 10,
+        'C' => 100,
+        'D' => 1000,
+        _ => unreachable!(),
+    }
+}
+
+fn target_room(amphipod: char) -> usize {
+    (amphipod as u8 - b'A') as usize
+}
+
+impl Burrow {
+    fn is_organized(&self) -> bool {
+        self.rooms
+            .iter()
+            .enumerate()
+            .all(|(r, room)| room.iter().all(|&a| a != EMPTY && target_room(a) == r))
+    }
+
+    // A room can be entered once it only holds amphipods that belong there.
+    fn is_enterable(&self, room: usize) -> bool {
+        self.rooms[room]
+            .iter()
+            .all(|&a| a == EMPTY || target_room(a) == room)
+    }
+
+    // Whether the hallway between from and to (excluding from itself) is clear.
+    fn is_clear(&self, from: usize, to: usize) -> bool {
+        let range = if from <= to {
+            from + 1..=to
+        } else {
+            to..=from - 1
+        };
+
+        self.hallway[range].iter().all(|&c| c == EMPTY)
+    }
+
+    // Moves an amphipod that can reach its own room there, returning the new burrow and
+    // the number of steps taken after leaving the hallway position start.
+    fn enter_room(&self, amphipod: char, start: usize) -> Option<(Self, usize)> {
+        let room = target_room(amphipod);
+
+        if !self.is_enterable(room) || !self.is_clear(start, ENTRANCES[room]) {
+            return None;
+        }
+
+        let depth = self.rooms[room].iter().rposition(|&a| a == EMPTY).unwrap();
+
+        let mut next = self.clone();
+        next.rooms[room][depth] = amphipod;
+
+        Some((next, start.abs_diff(ENTRANCES[room]) + depth + 1))
+    }
+
+    fn moves(&self) -> Vec<(Self, usize)> {
+        let mut moves = Vec::new();
+
+        // Amphipods in the hallway can only move into their own room.
+        self.hallway
+            .iter()
+            .enumerate()
+            .filter(|(_, &a)| a != EMPTY)
+            .for_each(|(h, &a)| {
+                let mut without = self.clone();
+                without.hallway[h] = EMPTY;
+
+                if let Some((next, steps)) = without.enter_room(a, h) {
+                    moves.push((next, steps * energy(a)));
+                }
+            });
+
+        // The top amphipod of a room that isn't finished yet can leave it,
+        // either straight into its own room or to a stop in the hallway.
+        self.rooms
+            .iter()
+            .enumerate()
+            .filter(|&(r, _)| !self.is_enterable(r))
+            .for_each(|(r, room)| {
+                let depth = room.iter().position(|&a| a != EMPTY).unwrap();
+                let a = room[depth];
+
+                let mut without = self.clone();
+                without.rooms[r][depth] = EMPTY;
+
+                if let Some((next, steps)) = without.enter_room(a, ENTRANCES[r]) {
+                    moves.push((next, (steps + depth + 1) * energy(a)));
+                    return;
+                }
+
+                (0..HALLWAY_LENGTH)
+                    .filter(|h| !ENTRANCES.contains(h))
+                    .filter(|&h| without.is_clear(ENTRANCES[r], h))
+                    .for_each(|h| {
+                        let mut next = without.clone();
+                        next.hallway[h] = a;
+
+                        moves.push((next, (ENTRANCES[r].abs_diff(h) + depth + 1) * energy(a)));
+                    });
+            });
+
+        moves
+    }
+
+    fn unfold(&self) -> Self {
+        let mut unfolded = self.clone();
+
+        unfolded.rooms.iter_mut().enumerate().for_each(|(r, room)| {
+            UNFOLDED
+                .iter()
+                .rev()
+                .for_each(|line| room.insert(1, line[r]))
+        });
+
+        unfolded
+    }
+}
+
+impl fmt::Display for Burrow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#############")?;
+        writeln!(f, "#{}#", self.hallway.iter().collect::<String>())?;
+
+        (0..self.rooms[0].len()).try_for_each(|depth| {
+            let (left, right) = if depth == 0 {
+                ("###", "###")
+            } else {
+                ("  #", "#")
+            };
+            writeln!(
+                f,
+                "{}{}{}",
+                left,
+                self.rooms.iter().map(|room| room[depth]).join("#"),
+                right
+            )
+        })?;
+
+        write!(f, "  #########")
+    }
+}
+
+// The space of all burrows, connected by single amphipod moves.
+struct Burrows;
+
+impl Graph for Burrows {
+    type Node = Burrow;
+
+    fn neighbours(&self, burrow: &Burrow) -> Vec<(Burrow, usize)> {
+        burrow.moves()
+    }
+}
+
+// Each burrow along the optimal route, paired with the energy spent on the move into it.
+type Moves = Vec<(Burrow, usize)>;
+
+fn organize(burrow: &Burrow) -> Option<Moves> {
+    dijkstra(&Burrows, [burrow.clone()], Burrow::is_organized).map(|path| path.steps)
+}
+
+fn total_energy(moves: &Moves) -> usize {
+    moves.iter().map(|(_, c)| c).sum()
+}
+
+fn describe(moves: &Moves) -> String {
+    moves
+        .iter()
+        .enumerate()
+        .scan(0, |total, (step, (burrow, cost))| {
+            *total += cost;
+            Some(format!(
+                "Move {}: {} energy ({} total)\n{}",
+                step, cost, total, burrow
+            ))
+        })
+        .join("\n\n")
+}
+
+fn part_1(burrow: &Burrow) -> Moves {
+    organize(burrow).unwrap()
+}
+
+fn part_2(burrow: &Burrow) -> Moves {
+    organize(&burrow.unfold()).unwrap()
+}
+
+fn parse_input(input: &str) -> Burrow {
+    let lines = input.lines().collect_vec();
+
+    let mut rooms: [Vec<char>; 4] = Default::default();
+    lines[2..lines.len() - 1].iter().for_each(|line| {
+        line.chars()
+            .filter(|c| c.is_ascii_uppercase())
+            .enumerate()
+            .for_each(|(r, a)| rooms[r].push(a))
+    });
+
+    Burrow {
+        hallway: [EMPTY; HALLWAY_LENGTH],
+        rooms,
+    }
 }
 
 pub fn solve() -> SolverResult {
-    println!("Part 1: {}", part_1());
-    println!("Part 2: {}", part_2());
+    solve_with(false)
+}
+
+// Optionally also shows every move the amphipods make, and the burrow after each one.
+pub fn solve_with(show_moves: bool) -> SolverResult {
+    let burrow = parse_input(&read_to_string("data/day_23.txt")?);
+
+    let moves = part_1(&burrow);
+    if show_moves {
+        println!("{}\n", describe(&moves));
+    }
+    println!("Part 1: {}", total_energy(&moves));
+
+    let moves = part_2(&burrow);
+    if show_moves {
+        println!("{}\n", describe(&moves));
+    }
+    println!("Part 2: {}", total_energy(&moves));
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+#############
+#...........#
+###B#C#B#D###
+  #A#D#C#A#
+  #########";
+
     #[test]
-    fn part_1_examples() {}
+    fn part_1_examples() {
+        let burrow = parse_input(INPUT);
+        assert_eq!(burrow.to_string(), INPUT);
+
+        let moves = part_1(&burrow);
+        assert_eq!(total_energy(&moves), 12521);
+        assert_eq!(moves.first().unwrap().0, burrow);
+        assert!(moves.last().unwrap().0.is_organized());
+    }
 
     #[test]
-    fn part_2_examples() {}
+    fn part_2_examples() {
+        let moves = part_2(&parse_input(INPUT));
+        assert_eq!(total_energy(&moves), 44169);
+        assert!(moves.last().unwrap().0.is_organized());
+    }
+
+    #[test]
+    fn moves_are_described() {
+        let description = describe(&part_1(&parse_input(INPUT)));
+
+        assert!(description.starts_with(&format!("Move 0: 0 energy (0 total)\n{}", INPUT)));
+        assert!(description.contains("(12521 total)\n#############\n#...........#\n###A#B#C#D###"));
+    }
 }
@@ -1,25 +1,1346 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::read_to_string;
+
+use itertools::Itertools;
+
 use crate::utils::SolverResult;
 
-fn part_1() -> usize {
-    0
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Register {
+    W,
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Operand {
+    Register(Register),
+    Literal(isize),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Instruction {
+    Inp(Register),
+    Add(Register, Operand),
+    Mul(Register, Operand),
+    Div(Register, Operand),
+    Mod(Register, Operand),
+    Eql(Register, Operand),
+}
+
+const REGISTERS: [Register; 4] = [Register::W, Register::X, Register::Y, Register::Z];
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Register::W => "w",
+            Register::X => "x",
+            Register::Y => "y",
+            Register::Z => "z",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "{}", r),
+            Operand::Literal(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Instruction {
+    fn target(&self) -> Register {
+        match *self {
+            Instruction::Inp(a)
+            | Instruction::Add(a, _)
+            | Instruction::Mul(a, _)
+            | Instruction::Div(a, _)
+            | Instruction::Mod(a, _)
+            | Instruction::Eql(a, _) => a,
+        }
+    }
+
+    fn operand(&self) -> Option<Operand> {
+        match *self {
+            Instruction::Inp(_) => None,
+            Instruction::Add(_, b)
+            | Instruction::Mul(_, b)
+            | Instruction::Div(_, b)
+            | Instruction::Mod(_, b)
+            | Instruction::Eql(_, b) => Some(b),
+        }
+    }
+
+    fn with_operand(&self, b: Operand) -> Self {
+        match *self {
+            Instruction::Inp(a) => Instruction::Inp(a),
+            Instruction::Add(a, _) => Instruction::Add(a, b),
+            Instruction::Mul(a, _) => Instruction::Mul(a, b),
+            Instruction::Div(a, _) => Instruction::Div(a, b),
+            Instruction::Mod(a, _) => Instruction::Mod(a, b),
+            Instruction::Eql(a, _) => Instruction::Eql(a, b),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Instruction::Inp(_) => "inp",
+            Instruction::Add(..) => "add",
+            Instruction::Mul(..) => "mul",
+            Instruction::Div(..) => "div",
+            Instruction::Mod(..) => "mod",
+            Instruction::Eql(..) => "eql",
+        };
+
+        match self.operand() {
+            Some(b) => write!(f, "{} {} {}", name, self.target(), b),
+            None => write!(f, "{} {}", name, self.target()),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum AluError {
+    MissingInput {
+        instruction: usize,
+    },
+    DivideByZero {
+        instruction: usize,
+    },
+    InvalidModulo {
+        instruction: usize,
+        a: isize,
+        b: isize,
+    },
+}
+
+impl fmt::Display for AluError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AluError::MissingInput { instruction } => {
+                write!(f, "ran out of input at instruction {}", instruction)
+            }
+            AluError::DivideByZero { instruction } => {
+                write!(f, "divided by zero at instruction {}", instruction)
+            }
+            AluError::InvalidModulo { instruction, a, b } => write!(
+                f,
+                "invalid modulo {} % {} at instruction {}",
+                a, b, instruction
+            ),
+        }
+    }
+}
+
+impl Error for AluError {}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+struct Alu {
+    registers: [isize; 4],
+}
+
+impl Alu {
+    fn get(&self, register: Register) -> isize {
+        self.registers[register as usize]
+    }
+
+    fn set(&mut self, register: Register, value: isize) {
+        self.registers[register as usize] = value;
+    }
+
+    fn value(&self, operand: Operand) -> isize {
+        match operand {
+            Operand::Register(r) => self.get(r),
+            Operand::Literal(v) => v,
+        }
+    }
+
+    fn run(
+        &mut self,
+        program: &[Instruction],
+        input: impl IntoIterator<Item = isize>,
+    ) -> Result<(), AluError> {
+        let mut input = input.into_iter();
+
+        program
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, &instruction)| match instruction {
+                Instruction::Inp(a) => {
+                    let v = input
+                        .next()
+                        .ok_or(AluError::MissingInput { instruction: i })?;
+                    self.set(a, v);
+                    Ok(())
+                }
+                Instruction::Add(a, b) => {
+                    self.set(a, self.get(a) + self.value(b));
+                    Ok(())
+                }
+                Instruction::Mul(a, b) => {
+                    self.set(a, self.get(a) * self.value(b));
+                    Ok(())
+                }
+                Instruction::Div(a, b) => match self.value(b) {
+                    0 => Err(AluError::DivideByZero { instruction: i }),
+                    // Rust's integer division already truncates toward zero.
+                    b => {
+                        self.set(a, self.get(a) / b);
+                        Ok(())
+                    }
+                },
+                Instruction::Mod(a, b) => match (self.get(a), self.value(b)) {
+                    (a_v, b_v) if a_v < 0 || b_v <= 0 => Err(AluError::InvalidModulo {
+                        instruction: i,
+                        a: a_v,
+                        b: b_v,
+                    }),
+                    (a_v, b_v) => {
+                        self.set(a, a_v % b_v);
+                        Ok(())
+                    }
+                },
+                Instruction::Eql(a, b) => {
+                    self.set(a, (self.get(a) == self.value(b)) as isize);
+                    Ok(())
+                }
+            })
+    }
+}
+
+// The inclusive range of values a register might hold at some point in the program.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Range {
+    min: isize,
+    max: isize,
+}
+
+impl Range {
+    const UNKNOWN: Range = Range {
+        min: isize::MIN,
+        max: isize::MAX,
+    };
+
+    // MONAD only ever reads non-zero digits.
+    const DIGIT: Range = Range { min: 1, max: 9 };
+
+    fn constant(value: isize) -> Self {
+        Range {
+            min: value,
+            max: value,
+        }
+    }
+
+    fn of(operand: Operand, ranges: &[Range; 4]) -> Self {
+        match operand {
+            Operand::Register(r) => ranges[r as usize],
+            Operand::Literal(v) => Range::constant(v),
+        }
+    }
+
+    fn value(&self) -> Option<isize> {
+        (self.min == self.max).then_some(self.min)
+    }
+
+    fn contains(&self, value: isize) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+
+    fn from_corners(corners: impl IntoIterator<Item = Option<isize>>) -> Self {
+        corners
+            .into_iter()
+            .try_fold(
+                Range {
+                    min: isize::MAX,
+                    max: isize::MIN,
+                },
+                |r, c| {
+                    c.map(|c| Range {
+                        min: r.min.min(c),
+                        max: r.max.max(c),
+                    })
+                },
+            )
+            .unwrap_or(Range::UNKNOWN)
+    }
+
+    // The possible results of applying the (non-input) instruction to a and b.
+    // Operations that might fail are UNKNOWN, so they never get folded away.
+    fn apply(instruction: &Instruction, a: Range, b: Range) -> Self {
+        let corners = |f: fn(isize, isize) -> Option<isize>| {
+            Range::from_corners([
+                f(a.min, b.min),
+                f(a.min, b.max),
+                f(a.max, b.min),
+                f(a.max, b.max),
+            ])
+        };
+
+        match instruction {
+            Instruction::Inp(_) => unreachable!(),
+            Instruction::Add(..) => Range {
+                min: a.min.saturating_add(b.min),
+                max: a.max.saturating_add(b.max),
+            },
+            Instruction::Mul(..) => corners(|a, b| Some(a.saturating_mul(b))),
+            Instruction::Div(..) if !b.contains(0) => corners(isize::checked_div),
+            Instruction::Div(..) => Range::UNKNOWN,
+            Instruction::Mod(..) if a.min >= 0 && b.min > 0 => {
+                if a.max < b.min {
+                    a
+                } else {
+                    Range {
+                        min: 0,
+                        max: b.max - 1,
+                    }
+                }
+            }
+            Instruction::Mod(..) => Range::UNKNOWN,
+            Instruction::Eql(..) => match (a.value(), b.value()) {
+                (Some(a), Some(b)) => Range::constant((a == b) as isize),
+                _ if a.max < b.min || b.max < a.min => Range::constant(0),
+                _ => Range { min: 0, max: 1 },
+            },
+        }
+    }
+}
+
+// Tracks the range of every register through the program,
+// replacing register operands with known values and dropping
+// instructions that can't change their register.
+fn propagate_constants(program: &[Instruction]) -> Vec<Instruction> {
+    let mut ranges = [Range::constant(0); 4];
+    let mut optimized = vec![];
+
+    for instruction in program {
+        let a = instruction.target();
+
+        let b = match instruction.operand() {
+            None => {
+                ranges[a as usize] = Range::DIGIT;
+                optimized.push(*instruction);
+                continue;
+            }
+            Some(Operand::Register(r)) => match ranges[r as usize].value() {
+                Some(v) => Operand::Literal(v),
+                None => Operand::Register(r),
+            },
+            Some(b) => b,
+        };
+        let instruction = instruction.with_operand(b);
+
+        let old = ranges[a as usize];
+        let new = Range::apply(&instruction, old, Range::of(b, &ranges));
+        ranges[a as usize] = new;
+
+        let is_identity = matches!(
+            instruction,
+            Instruction::Add(_, Operand::Literal(0))
+                | Instruction::Mul(_, Operand::Literal(1))
+                | Instruction::Div(_, Operand::Literal(1))
+        );
+        if is_identity || (old.value().is_some() && old == new) {
+            continue;
+        }
+
+        // If we know the result, we can set it without reading anything else.
+        optimized.push(match (old.value(), new.value()) {
+            (_, Some(0)) => Instruction::Mul(a, Operand::Literal(0)),
+            (Some(o), Some(n)) => Instruction::Add(a, Operand::Literal(n - o)),
+            _ => instruction,
+        });
+    }
+
+    optimized
+}
+
+// Walks backwards through the program, dropping instructions whose results are
+// overwritten before they are read. Every register counts as read at the end.
+fn eliminate_dead_stores(program: &[Instruction]) -> Vec<Instruction> {
+    // Instructions that might fail have to stay, even if nothing reads their result.
+    let mut ranges = [Range::constant(0); 4];
+    let may_fail = program
+        .iter()
+        .map(|instruction| {
+            let a = instruction.target() as usize;
+
+            match instruction.operand() {
+                None => {
+                    ranges[a] = Range::DIGIT;
+                    true
+                }
+                Some(b) => {
+                    let b = Range::of(b, &ranges);
+                    let may_fail = match instruction {
+                        Instruction::Div(..) => b.contains(0),
+                        Instruction::Mod(..) => ranges[a].min < 0 || b.min <= 0,
+                        _ => false,
+                    };
+                    ranges[a] = Range::apply(instruction, ranges[a], b);
+
+                    may_fail
+                }
+            }
+        })
+        .collect_vec();
+
+    let mut live = [true; 4];
+    let mut optimized = vec![];
+
+    for (instruction, may_fail) in program.iter().zip(may_fail).rev() {
+        let a = instruction.target() as usize;
+
+        if !live[a] && !may_fail {
+            continue;
+        }
+
+        live[a] = !matches!(
+            instruction,
+            Instruction::Inp(_) | Instruction::Mul(_, Operand::Literal(0))
+        );
+        if let Some(Operand::Register(r)) = instruction.operand() {
+            live[r as usize] = true;
+        }
+
+        optimized.push(*instruction);
+    }
+
+    optimized.reverse();
+
+    optimized
+}
+
+// Optimizes the program, assuming that every input is a non-zero digit.
+fn optimize(program: &[Instruction]) -> Vec<Instruction> {
+    let mut program = program.to_vec();
+
+    loop {
+        let optimized = eliminate_dead_stores(&propagate_constants(&program));
+
+        if optimized == program {
+            return program;
+        }
+
+        program = optimized;
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Op {
+    Add,
+    Mul,
+    Div,
+    Mod,
+    Eql,
+    Neq,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Op::Add => "+",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Mod => "%",
+            Op::Eql => "==",
+            Op::Neq => "!=",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Expression {
+    Literal(isize),
+    Input(usize),
+    // The operands are indexes of earlier expressions.
+    Binary(Op, usize, usize),
+}
+
+#[derive(Debug)]
+struct Node {
+    expression: Expression,
+    register: Register,
+    digit: Option<usize>,
+}
+
+// The program as a graph of expressions, where each register refers to
+// the node holding its current value.
+struct Listing {
+    nodes: Vec<Node>,
+    registers: [usize; 4],
+}
+
+impl Listing {
+    fn new(program: &[Instruction]) -> Self {
+        let mut listing = Listing {
+            nodes: vec![],
+            registers: [0; 4],
+        };
+
+        REGISTERS.iter().for_each(|&r| {
+            listing.registers[r as usize] = listing.push(Expression::Literal(0), r, None)
+        });
+
+        let mut digit = None;
+        program.iter().for_each(|instruction| {
+            let a = instruction.target();
+
+            let node = match instruction.operand() {
+                None => {
+                    let d = digit.map_or(0, |d| d + 1);
+                    digit = Some(d);
+                    listing.push(Expression::Input(d), a, digit)
+                }
+                Some(b) => {
+                    let b = match b {
+                        Operand::Register(r) => listing.registers[r as usize],
+                        Operand::Literal(v) => listing.push(Expression::Literal(v), a, digit),
+                    };
+                    let op = match instruction {
+                        Instruction::Add(..) => Op::Add,
+                        Instruction::Mul(..) => Op::Mul,
+                        Instruction::Div(..) => Op::Div,
+                        Instruction::Mod(..) => Op::Mod,
+                        Instruction::Eql(..) => Op::Eql,
+                        Instruction::Inp(_) => unreachable!(),
+                    };
+
+                    listing.binary(op, listing.registers[a as usize], b, a, digit)
+                }
+            };
+
+            listing.registers[a as usize] = node;
+        });
+
+        listing
+    }
+
+    fn push(&mut self, expression: Expression, register: Register, digit: Option<usize>) -> usize {
+        self.nodes.push(Node {
+            expression,
+            register,
+            digit,
+        });
+
+        self.nodes.len() - 1
+    }
+
+    // Builds a binary expression, simplifying it where possible.
+    fn binary(
+        &mut self,
+        op: Op,
+        a: usize,
+        b: usize,
+        register: Register,
+        digit: Option<usize>,
+    ) -> usize {
+        let folded = match (op, self.nodes[a].expression, self.nodes[b].expression) {
+            (Op::Add, Expression::Literal(x), Expression::Literal(y)) => Some(x + y),
+            (Op::Mul, Expression::Literal(x), Expression::Literal(y)) => Some(x * y),
+            (Op::Div, Expression::Literal(x), Expression::Literal(y)) if y != 0 => Some(x / y),
+            (Op::Mod, Expression::Literal(x), Expression::Literal(y)) if x >= 0 && y > 0 => {
+                Some(x % y)
+            }
+            (Op::Eql, Expression::Literal(x), Expression::Literal(y)) => Some((x == y) as isize),
+            (Op::Mul, Expression::Literal(0), _) | (Op::Mul, _, Expression::Literal(0)) => Some(0),
+            _ => None,
+        };
+        if let Some(v) = folded {
+            return self.push(Expression::Literal(v), register, digit);
+        }
+
+        match (op, self.nodes[a].expression, self.nodes[b].expression) {
+            (Op::Add, Expression::Literal(0), _) | (Op::Mul, Expression::Literal(1), _) => b,
+            (Op::Add, _, Expression::Literal(0))
+            | (Op::Mul, _, Expression::Literal(1))
+            | (Op::Div, _, Expression::Literal(1)) => a,
+            (Op::Eql, Expression::Binary(Op::Eql, x, y), Expression::Literal(0)) => {
+                self.push(Expression::Binary(Op::Neq, x, y), register, digit)
+            }
+            _ => self.push(Expression::Binary(op, a, b), register, digit),
+        }
+    }
+
+    fn render(&self, node: usize, names: &[Option<String>], top: bool) -> String {
+        match &names[node] {
+            Some(name) => name.clone(),
+            None => self.render_expression(node, names, top),
+        }
+    }
+
+    fn render_expression(&self, node: usize, names: &[Option<String>], top: bool) -> String {
+        match self.nodes[node].expression {
+            Expression::Literal(v) if v < 0 && !top => format!("({})", v),
+            Expression::Literal(v) => v.to_string(),
+            Expression::Input(d) => format!("input[{}]", d),
+            Expression::Binary(op, a, b) => {
+                let a = self.render(a, names, false);
+
+                let rendered = match (op, self.nodes[b].expression) {
+                    (Op::Add, Expression::Literal(v)) if v < 0 => format!("{} - {}", a, -v),
+                    (Op::Eql | Op::Neq, _) => {
+                        format!("({} {} {}) as isize", a, op, self.render(b, names, false))
+                    }
+                    _ => format!("{} {} {}", a, op, self.render(b, names, false)),
+                };
+
+                if top {
+                    rendered
+                } else {
+                    format!("({})", rendered)
+                }
+            }
+        }
+    }
+}
+
+// Renders the program as pseudo-Rust, with a group of statements for each input digit.
+// Values that are used more than once or carried over to a later digit
+// get their own variable, the rest are inlined.
+fn pseudo_rust(program: &[Instruction]) -> String {
+    let listing = Listing::new(program);
+    let nodes = &listing.nodes;
+
+    let roots = listing
+        .registers
+        .iter()
+        .copied()
+        .chain((0..nodes.len()).filter(|&n| matches!(nodes[n].expression, Expression::Input(_))))
+        .collect_vec();
+
+    let mut uses = vec![0; nodes.len()];
+    let mut crosses_digits = vec![false; nodes.len()];
+    let mut reachable = vec![false; nodes.len()];
+    let mut stack = roots.clone();
+    while let Some(n) = stack.pop() {
+        if reachable[n] {
+            continue;
+        }
+        reachable[n] = true;
+
+        if let Expression::Binary(_, a, b) = nodes[n].expression {
+            [a, b].into_iter().for_each(|operand| {
+                uses[operand] += 1;
+                crosses_digits[operand] |= nodes[operand].digit != nodes[n].digit;
+                stack.push(operand);
+            });
+        }
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let names = (0..nodes.len())
+        .map(|n| {
+            let is_named = match nodes[n].expression {
+                Expression::Literal(_) => false,
+                Expression::Input(_) => true,
+                Expression::Binary(..) => {
+                    reachable[n] && (uses[n] > 1 || crosses_digits[n] || roots.contains(&n))
+                }
+            };
+
+            is_named.then(|| {
+                let base = format!(
+                    "{}{}",
+                    nodes[n].register,
+                    nodes[n].digit.map_or(String::new(), |d| d.to_string())
+                );
+                let count = seen.entry(base.clone()).or_insert(0);
+                *count += 1;
+
+                match count {
+                    1 => base,
+                    _ => format!("{}_{}", base, *count - 1),
+                }
+            })
+        })
+        .collect_vec();
+
+    let mut lines = vec![];
+    (0..nodes.len())
+        .filter(|&n| names[n].is_some())
+        .for_each(|n| {
+            if let Expression::Input(d) = nodes[n].expression {
+                if d > 0 {
+                    lines.push(String::new());
+                }
+                lines.push(format!("// digit {}", d));
+            }
+
+            lines.push(format!(
+                "let {} = {};",
+                names[n].as_ref().unwrap(),
+                listing.render_expression(n, &names, true)
+            ));
+        });
+
+    lines.push(String::new());
+    lines.push(format!(
+        "({})",
+        listing
+            .registers
+            .iter()
+            .map(|&r| listing.render(r, &names, true))
+            .join(", ")
+    ));
+
+    lines.join("\n")
+}
+
+// Every digit of MONAD is checked by a copy of this block,
+// differing only in the three constants.
+const BLOCK: &str = "\
+inp w
+mul x 0
+add x z
+mod x 26
+div z {div_z}
+add x {add_x}
+eql x w
+eql x 0
+mul y 0
+add y 25
+mul y x
+add y 1
+mul z y
+mul y 0
+add y w
+add y {add_y}
+mul y x
+add z y";
+
+const BLOCK_LENGTH: usize = 18;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Block {
+    div_z: isize,
+    add_x: isize,
+    add_y: isize,
+}
+
+impl Block {
+    fn instructions(&self) -> Vec<Instruction> {
+        parse_input(
+            &BLOCK
+                .replace("{div_z}", &self.div_z.to_string())
+                .replace("{add_x}", &self.add_x.to_string())
+                .replace("{add_y}", &self.add_y.to_string()),
+        )
+        .expect("the block template parses")
+    }
+}
+
+fn literal(instruction: &Instruction) -> Option<isize> {
+    match instruction {
+        Instruction::Add(_, Operand::Literal(v)) | Instruction::Div(_, Operand::Literal(v)) => {
+            Some(*v)
+        }
+        _ => None,
+    }
+}
+
+// Splits the program into its per-digit blocks,
+// or returns None if it doesn't follow the usual MONAD shape.
+fn blocks(program: &[Instruction]) -> Option<Vec<Block>> {
+    if !program.len().is_multiple_of(BLOCK_LENGTH) {
+        return None;
+    }
+
+    program
+        .chunks(BLOCK_LENGTH)
+        .map(|chunk| {
+            let block = Block {
+                div_z: literal(&chunk[4])?,
+                add_x: literal(&chunk[5])?,
+                add_y: literal(&chunk[15])?,
+            };
+
+            (block.instructions() == chunk).then_some(block)
+        })
+        .collect()
+}
+
+// The digit at pop must equal the digit at push plus offset.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Constraint {
+    push: usize,
+    pop: usize,
+    offset: isize,
+}
+
+fn constraints(blocks: &[Block]) -> Option<Vec<Constraint>> {
+    // z is used as a stack of base-26 digits.
+    // A block with div z 1 has add_x >= 10, so its comparison can never succeed,
+    // and it always pushes digit + add_y.
+    // A block with div z 26 pops the top of the stack, and only avoids pushing
+    // again if its digit is the popped value plus add_x.
+    // For z to end at 0, every pop has to take the non-pushing branch.
+    let mut stack = vec![];
+    let mut constraints = vec![];
+
+    for (i, block) in blocks.iter().enumerate() {
+        match block.div_z {
+            1 if block.add_x >= 10 => stack.push(i),
+            26 => {
+                let push = stack.pop()?;
+                constraints.push(Constraint {
+                    push,
+                    pop: i,
+                    offset: blocks[push].add_y + block.add_x,
+                })
+            }
+            _ => return None,
+        }
+    }
+
+    stack.is_empty().then_some(constraints)
+}
+
+fn model_number(constraints: &[Constraint], largest: bool) -> Option<usize> {
+    let mut digits = vec![0; constraints.len() * 2];
+
+    for c in constraints {
+        let (push, pop) = match (largest, c.offset >= 0) {
+            (true, true) => (9 - c.offset, 9),
+            (true, false) => (9, 9 + c.offset),
+            (false, true) => (1, 1 + c.offset),
+            (false, false) => (1 - c.offset, 1),
+        };
+
+        if !(1..=9).contains(&push) || !(1..=9).contains(&pop) {
+            return None;
+        }
+
+        digits[c.push] = push;
+        digits[c.pop] = pop;
+    }
+
+    Some(digits.iter().fold(0, |n, &d| n * 10 + d as usize))
+}
+
+fn digits(number: usize) -> Vec<isize> {
+    number
+        .to_string()
+        .chars()
+        .map(|c| c.to_digit(10).unwrap() as isize)
+        .collect()
+}
+
+fn is_valid(program: &[Instruction], number: usize) -> bool {
+    let digits = digits(number);
+    let mut alu = Alu::default();
+
+    !digits.contains(&0) && alu.run(program, digits).is_ok() && alu.get(Register::Z) == 0
+}
+
+fn analyze(program: &[Instruction], largest: bool) -> Option<usize> {
+    let number = model_number(&constraints(&blocks(program)?)?, largest)?;
+
+    // The analysis makes assumptions about the program,
+    // so double-check the answer with the real thing.
+    is_valid(program, number).then_some(number)
+}
+
+// The instructions before the first input, and then the instructions
+// starting at each input up to the next one.
+fn split_at_inputs(program: &[Instruction]) -> (&[Instruction], Vec<&[Instruction]>) {
+    let starts = program
+        .iter()
+        .positions(|instruction| matches!(instruction, Instruction::Inp(_)))
+        .chain([program.len()])
+        .collect_vec();
+
+    (
+        &program[..starts[0]],
+        starts
+            .array_windows()
+            .map(|&[a, b]| &program[a..b])
+            .collect(),
+    )
+}
+
+// Which registers are read before being written, starting from each block.
+// At the end of the program only z matters.
+fn live_registers(blocks: &[&[Instruction]]) -> Vec<[bool; 4]> {
+    let mut live = [false, false, false, true];
+
+    let mut lives = blocks
+        .iter()
+        .rev()
+        .map(|block| {
+            block.iter().rev().for_each(|instruction| {
+                live[instruction.target() as usize] = !matches!(
+                    instruction,
+                    Instruction::Inp(_) | Instruction::Mul(_, Operand::Literal(0))
+                );
+                if let Some(Operand::Register(r)) = instruction.operand() {
+                    live[r as usize] = true;
+                }
+            });
+
+            live
+        })
+        .collect_vec();
+
+    lives.reverse();
+
+    lives
+}
+
+struct Search<'a> {
+    blocks: Vec<&'a [Instruction]>,
+    live: Vec<[bool; 4]>,
+    digits: Vec<isize>,
+    // States (with dead registers zeroed) that we know can't lead to a valid number.
+    failed: HashSet<(usize, Alu)>,
+}
+
+impl Search<'_> {
+    fn find(&mut self, block: usize, alu: Alu) -> Option<Vec<isize>> {
+        if block == self.blocks.len() {
+            return (alu.get(Register::Z) == 0).then(Vec::new);
+        }
+
+        let mut alu = alu;
+        REGISTERS
+            .iter()
+            .filter(|&&r| !self.live[block][r as usize])
+            .for_each(|&r| alu.set(r, 0));
+
+        if self.failed.contains(&(block, alu)) {
+            return None;
+        }
+
+        for d in self.digits.clone() {
+            let mut next = alu;
+            if next.run(self.blocks[block], [d]).is_err() {
+                continue;
+            }
+
+            if let Some(mut digits) = self.find(block + 1, next) {
+                digits.push(d);
+                return Some(digits);
+            }
+        }
+
+        self.failed.insert((block, alu));
+
+        None
+    }
+}
+
+// A depth-first search over the program one input at a time,
+// for when the program doesn't look like the MONAD that analyze expects.
+fn search(program: &[Instruction], largest: bool) -> Option<usize> {
+    let (prelude, blocks) = split_at_inputs(program);
+
+    let mut alu = Alu::default();
+    alu.run(prelude, []).ok()?;
+
+    let mut search = Search {
+        live: live_registers(&blocks),
+        blocks,
+        digits: if largest {
+            (1..=9).rev().collect()
+        } else {
+            (1..=9).collect()
+        },
+        failed: HashSet::new(),
+    };
+
+    let digits = search.find(0, alu)?;
+
+    Some(digits.iter().rev().fold(0, |n, &d| n * 10 + d as usize))
 }
 
-fn part_2() -> usize {
-    0
+fn part_1(program: &[Instruction]) -> usize {
+    analyze(program, true)
+        .or_else(|| search(program, true))
+        .unwrap()
+}
+
+fn part_2(program: &[Instruction]) -> usize {
+    analyze(program, false)
+        .or_else(|| search(program, false))
+        .unwrap()
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum ParseError {
+    UnknownRegister { line: usize, register: String },
+    UnknownInstruction { line: usize, instruction: String },
+    MissingInstruction { line: usize },
+    MissingOperand { line: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownRegister { line, register } => {
+                write!(f, "unknown register {:?} on line {}", register, line)
+            }
+            ParseError::UnknownInstruction { line, instruction } => {
+                write!(f, "unknown instruction {:?} on line {}", instruction, line)
+            }
+            ParseError::MissingInstruction { line } => {
+                write!(f, "missing instruction on line {}", line)
+            }
+            ParseError::MissingOperand { line } => write!(f, "missing operand on line {}", line),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+fn parse_register(register: &str, line: usize) -> Result<Register, ParseError> {
+    match register {
+        "w" => Ok(Register::W),
+        "x" => Ok(Register::X),
+        "y" => Ok(Register::Y),
+        "z" => Ok(Register::Z),
+        _ => Err(ParseError::UnknownRegister {
+            line,
+            register: register.to_owned(),
+        }),
+    }
+}
+
+fn parse_operand(operand: &str, line: usize) -> Result<Operand, ParseError> {
+    operand
+        .parse()
+        .map(Operand::Literal)
+        .or_else(|_| parse_register(operand, line).map(Operand::Register))
+}
+
+// Lines are numbered from 1, like in an editor.
+fn parse_input(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let line_number = i + 1;
+            let mut parts = line.split_whitespace();
+            let op = parts
+                .next()
+                .ok_or(ParseError::MissingInstruction { line: line_number })?;
+            let a = parse_register(
+                parts
+                    .next()
+                    .ok_or(ParseError::MissingOperand { line: line_number })?,
+                line_number,
+            )?;
+            let b = parts
+                .next()
+                .map(|b| parse_operand(b, line_number))
+                .transpose()?
+                .ok_or(ParseError::MissingOperand { line: line_number });
+
+            Ok(match op {
+                "inp" => Instruction::Inp(a),
+                "add" => Instruction::Add(a, b?),
+                "mul" => Instruction::Mul(a, b?),
+                "div" => Instruction::Div(a, b?),
+                "mod" => Instruction::Mod(a, b?),
+                "eql" => Instruction::Eql(a, b?),
+                _ => {
+                    return Err(ParseError::UnknownInstruction {
+                        line: line_number,
+                        instruction: op.to_owned(),
+                    })
+                }
+            })
+        })
+        .collect()
 }
 
 pub fn solve() -> SolverResult {
-    println!("Part 1: {}", part_1());
-    println!("Part 2: {}", part_2());
+    solve_with(false)
+}
+
+// Optionally also prints the optimized program as pseudo-Rust.
+pub fn solve_with(listing: bool) -> SolverResult {
+    let program = parse_input(&read_to_string("data/day_24.txt")?)?;
+
+    if listing {
+        println!("{}\n", pseudo_rust(&optimize(&program)));
+    }
+    println!("Part 1: {}", part_1(&program));
+    println!("Part 2: {}", part_2(&program));
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::utils::random::xorshift;
+
+    const NEGATE: &str = "\
+inp x
+mul x -1";
+
+    const THREE_TIMES: &str = "\
+inp z
+inp x
+mul z 3
+eql z x";
+
+    const BINARY: &str = "\
+inp w
+add z w
+mod z 2
+div w 2
+add y w
+mod y 2
+div w 2
+add x w
+mod x 2
+div w 2
+mod w 2";
+
+    fn parse(program: &str) -> Vec<Instruction> {
+        parse_input(program).unwrap()
+    }
+
+    fn run(program: &str, input: &[isize]) -> Result<Alu, AluError> {
+        let mut alu = Alu::default();
+        alu.run(&parse(program), input.iter().copied())?;
+        Ok(alu)
+    }
+
+    // There are no MONAD examples in the puzzle, so build one out of blocks
+    // with digit pairs (0, 13), (1, 12), (2, 11), (3, 8), (4, 5), (6, 7), and (9, 10).
+    const BLOCKS: [(isize, isize, isize); 14] = [
+        (1, 12, 4),
+        (1, 11, 11),
+        (1, 13, 5),
+        (1, 11, 11),
+        (1, 14, 14),
+        (26, -10, 7),
+        (1, 11, 11),
+        (26, -9, 4),
+        (26, -3, 6),
+        (1, 13, 5),
+        (26, -5, 9),
+        (26, -10, 12),
+        (26, -4, 14),
+        (26, -5, 14),
+    ];
+
+    // A shorter one, with digit pairs (0, 5), (1, 2), and (3, 4).
+    const SHORT_BLOCKS: [(isize, isize, isize); 6] = [
+        (1, 12, 4),
+        (1, 11, 11),
+        (26, -14, 7),
+        (1, 13, 5),
+        (26, -5, 9),
+        (26, -1, 12),
+    ];
+
+    fn monad_of(blocks: &[(isize, isize, isize)]) -> Vec<Instruction> {
+        blocks
+            .iter()
+            .flat_map(|&(div_z, add_x, add_y)| {
+                Block {
+                    div_z,
+                    add_x,
+                    add_y,
+                }
+                .instructions()
+            })
+            .collect()
+    }
+
+    fn monad() -> Vec<Instruction> {
+        monad_of(&BLOCKS)
+    }
+
+    #[test]
+    fn part_1_examples() {
+        let program = monad();
+        assert_eq!(part_1(&program), 92915979999498);
+        assert!(!is_valid(&program, 92915979999498 + 1));
+    }
+
+    #[test]
+    fn part_2_examples() {
+        let program = monad();
+        assert_eq!(part_2(&program), 21611513911181);
+        assert!(!is_valid(&program, 21611513911181 - 1));
+    }
+
+    fn random_digits(seed: u64) -> impl Iterator<Item = isize> {
+        xorshift(seed).map(|r| (r % 9 + 1) as isize)
+    }
+
+    #[test]
+    fn optimized_program_is_equivalent() {
+        let program = monad();
+        let optimized = optimize(&program);
+        assert!(optimized.len() < program.len() * 2 / 3);
+
+        (1..=1000).for_each(|seed| {
+            let digits = random_digits(seed).take(14).collect_vec();
+
+            let mut original = Alu::default();
+            original.run(&program, digits.clone()).unwrap();
+
+            let mut alu = Alu::default();
+            alu.run(&optimized, digits).unwrap();
+
+            assert_eq!(alu, original);
+        });
+    }
+
+    #[test]
+    fn optimizer_removes_redundant_instructions() {
+        assert_eq!(
+            optimize(&parse("inp w\nmul x 0\nadd x z\ndiv z 1\nadd y w\nadd z y")),
+            parse("inp w\nadd y w\nadd z y")
+        );
+        assert_eq!(
+            optimize(&parse("inp w\nadd x 12\neql x w\neql x 0")),
+            parse("inp w\nadd x 1")
+        );
+    }
+
+    #[test]
+    fn pseudo_rust_listing() {
+        let listing = pseudo_rust(&optimize(&monad()));
+
+        assert!(listing.starts_with(
+            "// digit 0\n\
+             let w0 = input[0];\n\
+             let y0 = w0 + 4;\n\
+             \n\
+             // digit 1\n\
+             let w1 = input[1];\n\
+             let z1 = (y0 * 26) + (w1 + 11);\n"
+        ));
+        assert!(listing.contains(
+            "// digit 5\n\
+             let w5 = input[5];\n\
+             let x5 = (((z4 % 26) - 10) != w5) as isize;\n\
+             let z5 = ((z4 / 26) * ((25 * x5) + 1)) + ((w5 + 7) * x5);\n"
+        ));
+        assert!(listing.ends_with("(w13, x13, y13, z13)"));
+    }
+
+    #[test]
+    fn search_examples() {
+        // The search can take a long time on a full-length MONAD.
+        let program = monad_of(&SHORT_BLOCKS);
+        assert_eq!(analyze(&program, true), Some(696999));
+        assert_eq!(analyze(&program, false), Some(141114));
+        assert_eq!(search(&program, true), Some(696999));
+        assert_eq!(search(&program, false), Some(141114));
+
+        // The optimized program doesn't have the usual block structure,
+        // but the search doesn't care.
+        let optimized = optimize(&program);
+        assert_eq!(blocks(&optimized), None);
+        assert_eq!(part_1(&optimized), 696999);
+        assert_eq!(part_2(&optimized), 141114);
+
+        // Two digits that add up to 10.
+        let program = parse("inp w\nadd z w\ninp w\nadd z w\neql z 10\neql z 0");
+        assert_eq!(part_1(&program), 91);
+        assert_eq!(part_2(&program), 19);
+        assert_eq!(search(&parse("inp w\nadd z 1"), true), None);
+    }
+
+    #[test]
+    fn analysis_rejects_other_programs() {
+        assert_eq!(blocks(&parse(BINARY)), None);
+
+        let mut program = monad();
+        program.swap(1, 2);
+        assert_eq!(analyze(&program, true), None);
+    }
+
+    #[test]
+    fn alu_examples() {
+        assert_eq!(run(NEGATE, &[7]).unwrap().get(Register::X), -7);
+
+        assert_eq!(run(THREE_TIMES, &[3, 9]).unwrap().get(Register::Z), 1);
+        assert_eq!(run(THREE_TIMES, &[3, 8]).unwrap().get(Register::Z), 0);
+
+        assert_eq!(run(BINARY, &[13]).unwrap().registers, [1, 1, 0, 1]);
+        assert_eq!(run(BINARY, &[6]).unwrap().registers, [0, 1, 1, 0]);
+    }
+
     #[test]
-    fn part_1_examples() {}
+    fn alu_errors() {
+        assert_eq!(
+            run(THREE_TIMES, &[3]),
+            Err(AluError::MissingInput { instruction: 1 })
+        );
+        assert_eq!(
+            run("inp x\ndiv x y", &[1]),
+            Err(AluError::DivideByZero { instruction: 1 })
+        );
+        assert_eq!(
+            run("inp x\nmod x 2", &[-3]),
+            Err(AluError::InvalidModulo {
+                instruction: 1,
+                a: -3,
+                b: 2
+            })
+        );
+        assert_eq!(
+            run("inp x\nmod x 0", &[3]),
+            Err(AluError::InvalidModulo {
+                instruction: 1,
+                a: 3,
+                b: 0
+            })
+        );
+    }
 
     #[test]
-    fn part_2_examples() {}
+    fn parse_errors() {
+        assert_eq!(
+            parse_input("inp w\nadd v 1"),
+            Err(ParseError::UnknownRegister {
+                line: 2,
+                register: "v".to_owned()
+            })
+        );
+        assert_eq!(
+            parse_input("inp w\nmul x q"),
+            Err(ParseError::UnknownRegister {
+                line: 2,
+                register: "q".to_owned()
+            })
+        );
+        assert_eq!(
+            parse_input("sub x 1"),
+            Err(ParseError::UnknownInstruction {
+                line: 1,
+                instruction: "sub".to_owned()
+            })
+        );
+        assert_eq!(
+            parse_input("inp w\n\nadd x"),
+            Err(ParseError::MissingInstruction { line: 2 })
+        );
+        assert_eq!(
+            parse_input("inp w\nadd"),
+            Err(ParseError::MissingOperand { line: 2 })
+        );
+        assert_eq!(
+            parse_input("inp w\nadd x").unwrap_err().to_string(),
+            "missing operand on line 2"
+        );
+    }
 }
@@ -1,42 +1,40 @@
+use crate::utils::grid::{Edges, Grid};
 use crate::utils::SolverResult;
-use std::collections::HashSet;
 use std::fs::read_to_string;
 use std::ops::ControlFlow::{Break, Continue};
 
-type Herd = HashSet<(usize, usize)>;
-type Herds = (Herd, Herd);
-type Bounds = (usize, usize);
-
-fn take_step(herds: &Herds, bounds: &Bounds) -> Herds {
-    let (x_bound, y_bound) = bounds;
-    let (east, south) = herds;
-    let (mut new_east, mut new_south) = (Herd::new(), Herd::new());
-
-    east.iter().for_each(|&(x, y)| {
-        let target = ((x + 1) % x_bound, y);
-        if east.contains(&target) || south.contains(&target) {
-            new_east.insert((x, y));
-        } else {
-            new_east.insert(target);
-        }
-    });
-
-    south.iter().for_each(|&(x, y)| {
-        let target = (x, (y + 1) % y_bound);
-        if new_east.contains(&target) || south.contains(&target) {
-            new_south.insert((x, y));
-        } else {
-            new_south.insert(target);
-        }
-    });
-
-    (new_east, new_south)
+const EAST: char = '>';
+const SOUTH: char = 'v';
+const EMPTY: char = '.';
+
+type Herds = Grid<char>;
+
+// Moves every cucumber in the herd facing the given way one step, if there's room.
+fn move_herd(herds: &Herds, herd: char, delta: (isize, isize)) -> Herds {
+    let mut moved = herds.clone();
+
+    herds
+        .iter()
+        .filter(|&(_, &c)| c == herd)
+        .for_each(|(p, _)| {
+            let target = herds.offset(p, delta, Edges::Wrapping).unwrap();
+            if herds[target] == EMPTY {
+                moved[p] = EMPTY;
+                moved[target] = herd;
+            }
+        });
+
+    moved
 }
 
-fn part_1(herds: &Herds, bounds: &Bounds) -> usize {
+fn take_step(herds: &Herds) -> Herds {
+    move_herd(&move_herd(herds, EAST, (1, 0)), SOUTH, (0, 1))
+}
+
+fn part_1(herds: &Herds) -> usize {
     (1..)
         .try_fold(herds.clone(), |herds, step| {
-            let new_herds = take_step(&herds, bounds);
+            let new_herds = take_step(&herds);
             if new_herds == herds {
                 Break(step)
             } else {
@@ -47,35 +45,17 @@ fn part_1(herds: &Herds, bounds: &Bounds) -> usize {
         .unwrap()
 }
 
-fn parse_input(input: &str) -> (Herds, Bounds) {
-    let herds = input.lines().enumerate().fold(
-        (Herd::new(), Herd::new()),
-        |(mut east, mut south), (y, line)| {
-            line.chars().enumerate().for_each(|(x, c)| {
-                match c {
-                    '>' => east.insert((x, y)),
-                    'v' => south.insert((x, y)),
-                    '.' => true,
-                    _ => unreachable!(),
-                };
-            });
-            (east, south)
-        },
-    );
-
-    (
-        herds,
-        (
-            input.lines().next().unwrap().chars().count(),
-            input.lines().count(),
-        ),
-    )
+fn parse_input(input: &str) -> Herds {
+    Grid::parse(input, |c| {
+        assert!([EAST, SOUTH, EMPTY].contains(&c));
+        c
+    })
 }
 
 pub fn solve() -> SolverResult {
-    let (herds, bounds) = parse_input(&read_to_string("data/day_25.txt")?);
+    let herds = parse_input(&read_to_string("data/day_25.txt")?);
 
-    println!("Part 1: {}", part_1(&herds, &bounds));
+    println!("Part 1: {}", part_1(&herds));
 
     Ok(())
 }
@@ -103,23 +83,17 @@ v.v..>>v.v
 
     #[test]
     fn part_1_examples() {
-        let (herds, bounds) = parse_input(SMALL_INPUT);
+        let herds = parse_input(SMALL_INPUT);
+        assert_eq!(herds.to_string(), SMALL_INPUT);
         assert_eq!(
-            herds,
-            (
-                HashSet::from_iter([(1, 1), (7, 2)].into_iter()),
-                HashSet::from_iter([(2, 1), (7, 1)].into_iter())
-            )
-        );
-        assert_eq!(
-            take_step(&herds, &bounds),
-            (
-                HashSet::from_iter([(1, 1), (8, 2)].into_iter()),
-                HashSet::from_iter([(2, 2), (7, 2)].into_iter())
-            )
+            take_step(&herds).to_string(),
+            "\
+..........
+.>........
+..v....v>.
+.........."
         );
 
-        let (herds, bounds) = parse_input(INPUT);
-        assert_eq!(part_1(&herds, &bounds), 58)
+        assert_eq!(part_1(&parse_input(INPUT)), 58)
     }
 }
@@ -56,28 +56,56 @@ lazy_static! {
     };
 }
 
+// The day that each day-specific flag belongs to.
+const DAY_FLAGS: [(&str, &str); 20] = [
+    ("days", "06"),
+    ("reset", "06"),
+    ("newborn", "06"),
+    ("modulo", "06"),
+    ("list", "12"),
+    ("dot", "12"),
+    ("revisits", "12"),
+    ("max-visits", "12"),
+    ("forbid", "12"),
+    ("images", "13"),
+    ("every-fold", "13"),
+    ("steps", "14"),
+    ("polymer", "14"),
+    ("element", "14"),
+    ("prefix", "14"),
+    ("render", "15"),
+    ("method", "22"),
+    ("export", "22"),
+    ("moves", "23"),
+    ("listing", "24"),
+];
+
 fn run_solver(day: &str) -> SolverResult {
     if let Some(solver) = SOLVERS.get(day) {
-        println!("★★ Day {} ★★★★★", day);
-        println!(
-            "★ https://adventofcode.com/2021/day/{}",
-            day.trim_start_matches('0')
-        );
-
-        let before = Instant::now();
-
-        let result = solver();
-
-        println!("★ Elapsed time: {:.2?}", before.elapsed());
-        println!("★★★★★★★★★★★★★★★");
-
-        result
+        run_timed(day, solver)
     } else {
         println!("Unknown day: {}", day);
         exit(1)
     }
 }
 
+fn run_timed(day: &str, solver: impl Fn() -> SolverResult) -> SolverResult {
+    println!("★★ Day {} ★★★★★", day);
+    println!(
+        "★ https://adventofcode.com/2021/day/{}",
+        day.trim_start_matches('0')
+    );
+
+    let before = Instant::now();
+
+    let result = solver();
+
+    println!("★ Elapsed time: {:.2?}", before.elapsed());
+    println!("★★★★★★★★★★★★★★★");
+
+    result
+}
+
 fn download_input(day: &str) -> Result<(), Box<dyn Error>> {
     dotenv::dotenv().ok();
 
@@ -129,6 +157,119 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .help("The day to run the solver for.")
                 .index(1),
         )
+        .arg(
+            Arg::with_name("days")
+                .long("days")
+                .takes_value(true)
+                .help("How many days to count lanternfish for on day 6."),
+        )
+        .arg(
+            Arg::with_name("reset")
+                .long("reset")
+                .takes_value(true)
+                .help("The timer lanternfish reset to after spawning on day 6."),
+        )
+        .arg(
+            Arg::with_name("newborn")
+                .long("newborn")
+                .takes_value(true)
+                .help("The timer newborn lanternfish start with on day 6."),
+        )
+        .arg(
+            Arg::with_name("modulo")
+                .long("modulo")
+                .help("Count lanternfish modulo 1000000007 on day 6."),
+        )
+        .arg(
+            Arg::with_name("method")
+                .long("method")
+                .takes_value(true)
+                .possible_values(&day_22::Method::NAMES)
+                .help("The algorithm to count cubes with on day 22."),
+        )
+        .arg(
+            Arg::with_name("export")
+                .long("export")
+                .help("Query the reactor and export the cubes left on to data/ on day 22."),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .help("Print every path through the caves on day 12."),
+        )
+        .arg(
+            Arg::with_name("dot")
+                .long("dot")
+                .help("Write the caves to data/day_12.dot on day 12."),
+        )
+        .arg(
+            Arg::with_name("images")
+                .long("images")
+                .help("Write the folded sheet as SVG and PBM images on day 13."),
+        )
+        .arg(
+            Arg::with_name("every-fold")
+                .long("every-fold")
+                .help("Write images of the sheet after every fold too on day 13."),
+        )
+        .arg(
+            Arg::with_name("steps")
+                .long("steps")
+                .takes_value(true)
+                .help("How many steps to grow the polymer for on day 14 (10 by default)."),
+        )
+        .arg(
+            Arg::with_name("polymer")
+                .long("polymer")
+                .help("Print the whole grown polymer on day 14."),
+        )
+        .arg(
+            Arg::with_name("element")
+                .long("element")
+                .takes_value(true)
+                .help("Print the element at this index (from 0) of the grown polymer on day 14."),
+        )
+        .arg(
+            Arg::with_name("prefix")
+                .long("prefix")
+                .takes_value(true)
+                .help("Print this many elements from the start of the grown polymer on day 14."),
+        )
+        .arg(
+            Arg::with_name("render")
+                .long("render")
+                .help("Show the lowest-risk paths and write them to data/ on day 15."),
+        )
+        .arg(
+            Arg::with_name("revisits")
+                .long("revisits")
+                .takes_value(true)
+                .help("How many small caves may be visited more than once on day 12."),
+        )
+        .arg(
+            Arg::with_name("max-visits")
+                .long("max-visits")
+                .takes_value(true)
+                .help("How many times those small caves may be visited on day 12."),
+        )
+        .arg(
+            Arg::with_name("forbid")
+                .long("forbid")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("A small cave that may never be revisited on day 12."),
+        )
+        .arg(
+            Arg::with_name("moves")
+                .long("moves")
+                .help("Show every move the amphipods make on day 23."),
+        )
+        .arg(
+            Arg::with_name("listing")
+                .long("listing")
+                .help("Print the optimized MONAD as pseudo-Rust on day 24."),
+        )
         .get_matches();
 
     if let Some(matches) = matches.subcommand_matches("get-input") {
@@ -140,8 +281,106 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .sorted()
                 .try_for_each(|day| download_input(day))?
         };
+    } else if let Some((flag, flag_day)) = DAY_FLAGS.iter().find(|(flag, flag_day)| {
+        matches.is_present(flag)
+            && matches
+                .value_of("DAY")
+                .map(|d| format!("{:0>2}", d))
+                .as_deref()
+                != Some(flag_day)
+    }) {
+        println!(
+            "--{} only applies to day {}",
+            flag,
+            flag_day.trim_start_matches('0')
+        );
+        exit(1)
     } else if let Some(day) = matches.value_of("DAY").map(|d| format!("{:0>2}", d)) {
-        run_solver(&day)?;
+        let is_present = |args: &[&str]| args.iter().any(|arg| matches.is_present(arg));
+
+        match day.as_str() {
+            "06" if is_present(&["days", "reset", "newborn", "modulo"]) => {
+                let default = day_06::Lifecycle::default();
+                let lifecycle = day_06::Lifecycle {
+                    reset: matches
+                        .value_of("reset")
+                        .map(str::parse)
+                        .transpose()?
+                        .unwrap_or(default.reset),
+                    newborn: matches
+                        .value_of("newborn")
+                        .map(str::parse)
+                        .transpose()?
+                        .unwrap_or(default.newborn),
+                };
+                let days = matches
+                    .value_of("days")
+                    .map(str::parse)
+                    .transpose()?
+                    .unwrap_or(256);
+                let modulo = matches.is_present("modulo");
+                run_timed(&day, || day_06::solve_with(&lifecycle, days, modulo))?
+            }
+            "12" if is_present(&["list", "dot", "revisits", "max-visits", "forbid"]) => {
+                let default = day_12::Policy::default();
+                let policy = day_12::Policy {
+                    revisits: matches
+                        .value_of("revisits")
+                        .map(str::parse)
+                        .transpose()?
+                        .unwrap_or(default.revisits),
+                    max_visits: matches
+                        .value_of("max-visits")
+                        .map(str::parse)
+                        .transpose()?
+                        .unwrap_or(default.max_visits),
+                    forbidden: matches
+                        .values_of("forbid")
+                        .map(|caves| caves.map(str::to_owned).collect())
+                        .unwrap_or(default.forbidden),
+                };
+
+                run_timed(&day, || {
+                    day_12::solve_with(
+                        &policy,
+                        matches.is_present("list"),
+                        matches.is_present("dot"),
+                    )
+                })?
+            }
+            "13" if is_present(&["images", "every-fold"]) => run_timed(&day, || {
+                day_13::solve_with(
+                    matches.is_present("images"),
+                    matches.is_present("every-fold"),
+                )
+            })?,
+            "14" if is_present(&["steps", "polymer", "element", "prefix"]) => {
+                let steps = matches
+                    .value_of("steps")
+                    .map(str::parse)
+                    .transpose()?
+                    .unwrap_or(10);
+                let element = matches.value_of("element").map(str::parse).transpose()?;
+                let prefix = matches.value_of("prefix").map(str::parse).transpose()?;
+                run_timed(&day, || {
+                    day_14::solve_with(steps, matches.is_present("polymer"), element, prefix)
+                })?
+            }
+            "15" if is_present(&["render"]) => run_timed(&day, || day_15::solve_with(true))?,
+            "22" if is_present(&["method", "export"]) => {
+                let method = matches
+                    .value_of("method")
+                    .map(str::parse::<day_22::Method>)
+                    .transpose()?
+                    .unwrap_or_default();
+                run_timed(&day, || {
+                    day_22::solve_with(method, matches.is_present("export"))
+                })?
+            }
+            "23" if is_present(&["moves"]) => run_timed(&day, || day_23::solve_with(true))?,
+            "24" if is_present(&["listing"]) => run_timed(&day, || day_24::solve_with(true))?,
+            _ => run_solver(&day)?,
+        }
     } else {
         SOLVERS
             .keys()
@@ -1,3 +1,10 @@
 use std::error::Error;
 
+pub mod graph;
+pub mod grid;
+pub mod matrix;
+pub mod modular;
+#[cfg(test)]
+pub mod random;
+
 pub type SolverResult = Result<(), Box<dyn Error>>;
@@ -0,0 +1,273 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+// A graph that's explored lazily, one node's outgoing edges at a time.
+pub trait Graph {
+    type Node: Clone + Eq + Hash + Ord;
+
+    // The nodes reachable in one step from node, each paired with the cost of getting there.
+    fn neighbours(&self, node: &Self::Node) -> Vec<(Self::Node, usize)>;
+}
+
+// A graph whose nodes are numbered 0..size, so a search can keep what it knows
+// about them in vectors rather than hash maps.
+pub trait IndexedGraph: Graph {
+    fn size(&self) -> usize;
+
+    fn index(&self, node: &Self::Node) -> usize;
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Path<N> {
+    // Each node along the path, paired with the cost of the edge into it
+    // (the first node is a start node, so its cost is 0).
+    pub steps: Vec<(N, usize)>,
+    pub cost: usize,
+}
+
+impl<N> Path<N> {
+    pub fn nodes(&self) -> impl Iterator<Item = &N> {
+        self.steps.iter().map(|(n, _)| n)
+    }
+}
+
+// https://doc.rust-lang.org/std/collections/binary_heap/index.html
+#[derive(Clone, Eq, PartialEq)]
+struct State<N> {
+    node: N,
+    cost: usize,
+    estimate: usize,
+}
+
+impl<N: Ord> PartialOrd for State<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: Ord> Ord for State<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+// The cheapest path from any of the starts to any node satisfying is_goal.
+pub fn dijkstra<G: Graph>(
+    graph: &G,
+    starts: impl IntoIterator<Item = G::Node>,
+    is_goal: impl Fn(&G::Node) -> bool,
+) -> Option<Path<G::Node>> {
+    a_star(graph, starts, is_goal, |_| 0)
+}
+
+// Like dijkstra, but guided by a heuristic that must never overestimate the
+// remaining cost to a goal, or the path found might not be the cheapest.
+pub fn a_star<G: Graph>(
+    graph: &G,
+    starts: impl IntoIterator<Item = G::Node>,
+    is_goal: impl Fn(&G::Node) -> bool,
+    heuristic: impl Fn(&G::Node) -> usize,
+) -> Option<Path<G::Node>> {
+    let mut frontier = BinaryHeap::new();
+    let mut shortest_paths = HashMap::new();
+    let mut predecessors: HashMap<G::Node, (G::Node, usize)> = HashMap::new();
+
+    starts.into_iter().for_each(|start| {
+        frontier.push(State {
+            estimate: heuristic(&start),
+            node: start.clone(),
+            cost: 0,
+        });
+        shortest_paths.insert(start, 0);
+    });
+
+    while let Some(current) = frontier.pop() {
+        // Stale entry; we've already found a cheaper way here.
+        if current.cost > shortest_paths[&current.node] {
+            continue;
+        }
+
+        if is_goal(&current.node) {
+            let mut steps = vec![];
+            let mut node = current.node;
+
+            while let Some((prev, cost)) = predecessors.remove(&node) {
+                steps.push((node, cost));
+                node = prev;
+            }
+            steps.push((node, 0));
+            steps.reverse();
+
+            return Some(Path {
+                steps,
+                cost: current.cost,
+            });
+        }
+
+        graph
+            .neighbours(&current.node)
+            .into_iter()
+            .for_each(|(n, c)| {
+                let cost = current.cost + c;
+
+                if cost < *shortest_paths.get(&n).unwrap_or(&usize::MAX) {
+                    shortest_paths.insert(n.clone(), cost);
+                    predecessors.insert(n.clone(), (current.node.clone(), c));
+                    frontier.push(State {
+                        estimate: cost + heuristic(&n),
+                        node: n,
+                        cost,
+                    });
+                }
+            });
+    }
+
+    None
+}
+
+// Like dijkstra, but for an indexed graph whose edges all cost at most max_cost.
+// The tentative costs on the frontier are then never more than max_cost apart, so
+// it can be a ring of max_cost + 1 buckets indexed by cost instead of a heap.
+pub fn dijkstra_bounded<G: IndexedGraph>(
+    graph: &G,
+    starts: impl IntoIterator<Item = G::Node>,
+    is_goal: impl Fn(&G::Node) -> bool,
+    max_cost: usize,
+) -> Option<Path<G::Node>> {
+    let mut shortest_paths = vec![usize::MAX; graph.size()];
+    let mut predecessors: Vec<Option<(G::Node, usize)>> = vec![None; graph.size()];
+    let mut buckets = vec![vec![]; max_cost + 1];
+    let mut queued = 0;
+
+    starts.into_iter().for_each(|start| {
+        shortest_paths[graph.index(&start)] = 0;
+        buckets[0].push(start);
+        queued += 1;
+    });
+
+    let mut cost = 0;
+    while queued > 0 {
+        while let Some(node) = buckets[cost % (max_cost + 1)].pop() {
+            queued -= 1;
+
+            // Stale entry; we've already found a cheaper way here.
+            if shortest_paths[graph.index(&node)] < cost {
+                continue;
+            }
+
+            if is_goal(&node) {
+                let mut steps = vec![];
+                let mut node = node;
+
+                while let Some((prev, c)) = predecessors[graph.index(&node)].take() {
+                    steps.push((node, c));
+                    node = prev;
+                }
+                steps.push((node, 0));
+                steps.reverse();
+
+                return Some(Path { steps, cost });
+            }
+
+            graph.neighbours(&node).into_iter().for_each(|(n, c)| {
+                assert!(c <= max_cost, "edge costs {}, more than {}", c, max_cost);
+
+                let next = cost + c;
+                let index = graph.index(&n);
+
+                if next < shortest_paths[index] {
+                    shortest_paths[index] = next;
+                    predecessors[index] = Some((node.clone(), c));
+                    buckets[next % (max_cost + 1)].push(n);
+                    queued += 1;
+                }
+            });
+        }
+
+        cost += 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A line of nodes 0..length, where stepping right costs 1 and jumping ahead
+    // by jump costs jump_cost.
+    struct Line {
+        length: usize,
+        jump: usize,
+        jump_cost: usize,
+    }
+
+    impl Graph for Line {
+        type Node = usize;
+
+        fn neighbours(&self, &node: &usize) -> Vec<(usize, usize)> {
+            [(node + 1, 1), (node + self.jump, self.jump_cost)]
+                .into_iter()
+                .filter(|&(n, _)| n < self.length)
+                .collect()
+        }
+    }
+
+    impl IndexedGraph for Line {
+        fn size(&self) -> usize {
+            self.length
+        }
+
+        fn index(&self, &node: &usize) -> usize {
+            node
+        }
+    }
+
+    #[test]
+    fn finds_cheapest_path() {
+        let line = Line {
+            length: 10,
+            jump: 3,
+            jump_cost: 2,
+        };
+
+        let path = dijkstra(&line, [0], |&n| n == 9).unwrap();
+        assert_eq!(path.cost, 6);
+        assert_eq!(path.steps, vec![(0, 0), (3, 2), (6, 2), (9, 2)]);
+        assert_eq!(
+            a_star(&line, [0], |&n| n == 9, |&n| (9 - n) / 3),
+            Some(path.clone())
+        );
+        assert_eq!(dijkstra_bounded(&line, [0], |&n| n == 9, 2), Some(path));
+    }
+
+    #[test]
+    fn starts_and_goals() {
+        let line = Line {
+            length: 10,
+            jump: 4,
+            jump_cost: 10,
+        };
+
+        let path = dijkstra(&line, [1, 6], |&n| n == 3 || n == 9).unwrap();
+        assert_eq!(path.cost, 2);
+        assert_eq!(path.nodes().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        assert_eq!(dijkstra(&line, [5], |&n| n == 2), None);
+        assert_eq!(dijkstra(&line, [2], |&n| n == 2).map(|p| p.cost), Some(0));
+
+        assert_eq!(
+            dijkstra_bounded(&line, [1, 6], |&n| n == 3 || n == 9, 10),
+            Some(path)
+        );
+        assert_eq!(dijkstra_bounded(&line, [5], |&n| n == 2, 10), None);
+        assert_eq!(
+            dijkstra_bounded(&line, [2], |&n| n == 2, 10).map(|p| p.cost),
+            Some(0)
+        );
+    }
+}
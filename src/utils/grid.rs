@@ -0,0 +1,255 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use itertools::Itertools;
+
+// Positions are (x, y), with x counting columns from the left and y counting rows from the top.
+pub type Position = (usize, usize);
+
+// The four orthogonal neighbours, in reading order.
+pub const ORTHOGONAL: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+// All eight neighbours, including diagonals, in reading order.
+pub const SURROUNDING: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+// What happens when an offset walks off the side of the grid.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Edges {
+    // There's nothing there.
+    Bounded,
+    // It comes back around on the opposite side.
+    Wrapping,
+}
+
+// A dense, rectangular grid, stored row by row.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn from_fn(width: usize, height: usize, f: impl FnMut(Position) -> T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(f)
+                .collect(),
+        }
+    }
+
+    // Parses a grid with a character per cell and a line per row.
+    pub fn parse(input: &str, f: impl FnMut(char) -> T) -> Self {
+        let lines = input.lines().collect_vec();
+        let width = lines.first().map_or(0, |line| line.chars().count());
+
+        let cells = lines
+            .iter()
+            .flat_map(|line| {
+                assert_eq!(line.chars().count(), width, "rows have different lengths");
+                line.chars()
+            })
+            .map(f)
+            .collect();
+
+        Grid {
+            width,
+            height: lines.len(),
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn get(&self, (x, y): Position) -> Option<&T> {
+        (x < self.width && y < self.height).then(|| &self.cells[y * self.width + x])
+    }
+
+    pub fn get_mut(&mut self, (x, y): Position) -> Option<&mut T> {
+        (x < self.width && y < self.height).then(|| &mut self.cells[y * self.width + x])
+    }
+
+    // Every position, in reading order.
+    pub fn positions(&self) -> impl Iterator<Item = Position> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    // The position reached by moving (dx, dy) from p, if it's on the grid.
+    pub fn offset(
+        &self,
+        (x, y): Position,
+        (dx, dy): (isize, isize),
+        edges: Edges,
+    ) -> Option<Position> {
+        let (x, y) = (x as isize + dx, y as isize + dy);
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        match edges {
+            Edges::Bounded => ((0..width).contains(&x) && (0..height).contains(&y))
+                .then_some((x as usize, y as usize)),
+            // An empty grid has nowhere to wrap around to.
+            Edges::Wrapping if self.cells.is_empty() => None,
+            Edges::Wrapping => Some((x.rem_euclid(width) as usize, y.rem_euclid(height) as usize)),
+        }
+    }
+
+    // The positions at each of the given offsets from p that are on the grid,
+    // typically ORTHOGONAL or SURROUNDING.
+    pub fn neighbours<'a>(
+        &'a self,
+        p: Position,
+        offsets: &'a [(isize, isize)],
+        edges: Edges,
+    ) -> impl Iterator<Item = Position> + 'a {
+        offsets
+            .iter()
+            .filter_map(move |&delta| self.offset(p, delta, edges))
+    }
+}
+
+impl Grid<u8> {
+    // Parses a grid with a single digit per cell.
+    pub fn parse_digits(input: &str) -> Self {
+        Grid::parse(input, |c| c.to_digit(10).unwrap() as u8)
+    }
+}
+
+impl<T> Index<Position> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, p: Position) -> &T {
+        self.get(p)
+            .unwrap_or_else(|| panic!("{:?} is outside the grid", p))
+    }
+}
+
+impl<T> IndexMut<Position> for Grid<T> {
+    fn index_mut(&mut self, p: Position) -> &mut T {
+        self.get_mut(p)
+            .unwrap_or_else(|| panic!("{:?} is outside the grid", p))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.cells
+                .chunks(self.width.max(1))
+                .map(|row| row.iter().join(""))
+                .join("\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+123
+456";
+
+    #[test]
+    fn parse_and_display() {
+        let grid = Grid::parse_digits(INPUT);
+
+        assert_eq!((grid.width(), grid.height(), grid.len()), (3, 2, 6));
+        assert_eq!(grid[(2, 0)], 3);
+        assert_eq!(grid[(0, 1)], 4);
+        assert_eq!(grid.get((3, 0)), None);
+        assert_eq!(grid.to_string(), INPUT);
+
+        assert_eq!(
+            Grid::parse(".#\n#.", |c| c == '#')
+                .map(|&b| if b { 'X' } else { 'O' })
+                .to_string(),
+            "OX\nXO"
+        );
+        assert_eq!(
+            Grid::from_fn(2, 2, |(x, y)| x * 2 + y).to_string(),
+            "02\n13"
+        );
+    }
+
+    #[test]
+    fn neighbours() {
+        let grid = Grid::parse_digits(INPUT);
+
+        assert_eq!(
+            grid.neighbours((0, 0), &ORTHOGONAL, Edges::Bounded)
+                .collect_vec(),
+            vec![(1, 0), (0, 1)]
+        );
+        assert_eq!(
+            grid.neighbours((0, 0), &ORTHOGONAL, Edges::Wrapping)
+                .collect_vec(),
+            vec![(0, 1), (2, 0), (1, 0), (0, 1)]
+        );
+        assert_eq!(
+            grid.neighbours((1, 1), &SURROUNDING, Edges::Bounded)
+                .map(|p| grid[p])
+                .collect_vec(),
+            vec![1, 2, 3, 4, 6]
+        );
+        assert_eq!(grid.offset((2, 1), (1, 1), Edges::Wrapping), Some((0, 0)));
+        assert_eq!(grid.offset((2, 1), (1, 1), Edges::Bounded), None);
+
+        let empty = Grid::parse_digits("");
+        assert_eq!(empty.offset((0, 0), (1, 0), Edges::Wrapping), None);
+        assert_eq!(
+            empty
+                .neighbours((0, 0), &SURROUNDING, Edges::Wrapping)
+                .count(),
+            0
+        );
+    }
+}
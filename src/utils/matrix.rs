@@ -0,0 +1,189 @@
+use std::ops::{Index, IndexMut, Mul};
+
+use num::{One, Zero};
+
+// A dense, square matrix, stored row by row and indexed by (row, column).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Matrix<T> {
+    size: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Matrix<T> {
+    pub fn from_fn(size: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        Matrix {
+            size,
+            cells: (0..size)
+                .flat_map(|row| (0..size).map(move |column| (row, column)))
+                .map(|(row, column)| f(row, column))
+                .collect(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T: Zero + One> Matrix<T> {
+    pub fn zero(size: usize) -> Self {
+        Matrix::from_fn(size, |_, _| T::zero())
+    }
+
+    pub fn identity(size: usize) -> Self {
+        Matrix::from_fn(
+            size,
+            |row, column| {
+                if row == column {
+                    T::one()
+                } else {
+                    T::zero()
+                }
+            },
+        )
+    }
+}
+
+impl<T: Clone + Zero + One> Matrix<T>
+where
+    for<'a> &'a T: Mul<Output = T>,
+{
+    // Exponentiation by squaring, so this takes O(log exponent) multiplications.
+    pub fn pow(&self, mut exponent: u64) -> Self {
+        let mut result = Self::identity(self.size);
+        let mut square = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &square;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                square = &square * &square;
+            }
+        }
+
+        result
+    }
+
+    // The same as self.pow(exponent).apply(vector), but only the squares are multiplied as
+    // matrices, which is much cheaper when the entries are big.
+    pub fn pow_apply(&self, mut exponent: u64, vector: &[T]) -> Vec<T> {
+        let mut result = vector.to_vec();
+        let mut square = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = square.apply(&result);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                square = &square * &square;
+            }
+        }
+
+        result
+    }
+
+    // The product of this matrix and a column vector.
+    pub fn apply(&self, vector: &[T]) -> Vec<T> {
+        assert_eq!(vector.len(), self.size, "vector is the wrong length");
+
+        self.cells
+            .chunks(self.size.max(1))
+            .map(|row| {
+                row.iter()
+                    .zip(vector)
+                    .filter(|(a, b)| !a.is_zero() && !b.is_zero())
+                    .fold(T::zero(), |sum, (a, b)| sum + a * b)
+            })
+            .collect()
+    }
+}
+
+impl<'a, T: Clone + Zero + One> Mul for &'a Matrix<T>
+where
+    for<'b> &'b T: Mul<Output = T>,
+{
+    type Output = Matrix<T>;
+
+    fn mul(self, other: &'a Matrix<T>) -> Matrix<T> {
+        assert_eq!(self.size, other.size, "matrices are different sizes");
+
+        // Transition matrices are mostly zeros, so skip those rather than multiplying by them.
+        let mut product = Matrix::<T>::zero(self.size);
+        for row in 0..self.size {
+            for k in (0..self.size).filter(|&k| !self[(row, k)].is_zero()) {
+                for column in (0..self.size).filter(|&column| !other[(k, column)].is_zero()) {
+                    let term = &self[(row, k)] * &other[(k, column)];
+                    let cell = &mut product[(row, column)];
+                    *cell = std::mem::replace(cell, T::zero()) + term;
+                }
+            }
+        }
+
+        product
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (row, column): (usize, usize)) -> &T {
+        assert!(
+            row < self.size && column < self.size,
+            "{:?} is outside the matrix",
+            (row, column)
+        );
+        &self.cells[row * self.size + column]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut T {
+        assert!(
+            row < self.size && column < self.size,
+            "{:?} is outside the matrix",
+            (row, column)
+        );
+        &mut self.cells[row * self.size + column]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::BigUint;
+
+    #[test]
+    fn fibonacci() {
+        let fibonacci = Matrix::from_fn(2, |row, column| if row + column < 2 { 1u64 } else { 0 });
+
+        assert_eq!(fibonacci.pow(0), Matrix::identity(2));
+        assert_eq!(fibonacci.pow(1), fibonacci);
+        assert_eq!(fibonacci.pow(10).apply(&[1, 0]), vec![89, 55]);
+        assert_eq!(fibonacci.pow_apply(10, &[1, 0]), vec![89, 55]);
+        assert_eq!(fibonacci.pow(90)[(0, 1)], 2880067194370816120);
+
+        let fibonacci = Matrix::from_fn(2, |row, column| {
+            BigUint::from(if row + column < 2 { 1u8 } else { 0 })
+        });
+        assert_eq!(
+            fibonacci.pow(300)[(0, 1)].to_string(),
+            "222232244629420445529739893461909967206666939096499764990979600"
+        );
+    }
+
+    #[test]
+    fn products() {
+        let a = Matrix::from_fn(3, |row, column| (row * 3 + column) as u64);
+
+        assert_eq!(&a * &Matrix::identity(3), a);
+        assert_eq!(
+            &a * &a,
+            Matrix::from_fn(3, |row, column| [[15, 18, 21], [42, 54, 66], [69, 90, 111]]
+                [row][column])
+        );
+        assert_eq!(a.pow(3), &(&a * &a) * &a);
+    }
+}
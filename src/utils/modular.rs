@@ -0,0 +1,94 @@
+use std::fmt;
+use std::ops::{Add, Mul};
+
+use num::{One, Zero};
+
+// The usual prime for keeping enormous counts down to size.
+pub const PRIME: u64 = 1_000_000_007;
+
+// An integer modulo M, which must be small enough that the product of two residues fits in a u128.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Modular<const M: u64>(u64);
+
+impl<const M: u64> Modular<M> {
+    pub fn new(value: u64) -> Self {
+        Modular(value % M)
+    }
+
+    pub fn value(self) -> u64 {
+        self.0
+    }
+}
+
+impl<const M: u64> From<u64> for Modular<M> {
+    fn from(value: u64) -> Self {
+        Modular::new(value)
+    }
+}
+
+impl<const M: u64> Add for Modular<M> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Modular(((self.0 as u128 + other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Mul for Modular<M> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Modular(((self.0 as u128 * other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Mul for &Modular<M> {
+    type Output = Modular<M>;
+
+    fn mul(self, other: Self) -> Modular<M> {
+        *self * *other
+    }
+}
+
+impl<const M: u64> Zero for Modular<M> {
+    fn zero() -> Self {
+        Modular(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl<const M: u64> One for Modular<M> {
+    fn one() -> Self {
+        Modular::new(1)
+    }
+}
+
+impl<const M: u64> fmt::Display for Modular<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (mod {})", self.0, M)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        type P = Modular<PRIME>;
+
+        assert_eq!(P::new(PRIME + 5).value(), 5);
+        assert_eq!(P::new(PRIME - 1) + P::new(3), P::new(2));
+        assert_eq!(P::new(PRIME - 1) * P::new(PRIME - 1), P::one());
+        assert_eq!(P::new(1 << 40) * P::new(1 << 40), P::new(496641140));
+        assert!((P::new(7) * P::zero()).is_zero());
+        assert_eq!(P::new(12).to_string(), "12 (mod 1000000007)");
+
+        // 2^64, which doesn't fit in a u64
+        assert_eq!((0..64).fold(P::one(), |p, _| p + p), P::new(582344008));
+        assert_eq!(Modular::<7>::new(3) * Modular::new(5), Modular::new(1));
+    }
+}
@@ -0,0 +1,24 @@
+// A small xorshift generator, so that tests can make up inputs without needing a dependency.
+// The seed must not be 0, or every number is 0.
+pub fn xorshift(seed: u64) -> impl Iterator<Item = u64> {
+    (0..).scan(seed, |state, _| {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        Some(*state)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(
+            xorshift(1).take(3).collect::<Vec<_>>(),
+            vec![1082269761, 1152992998833853505, 11177516664432764457]
+        );
+        assert!(xorshift(42).zip(xorshift(43)).take(10).all(|(a, b)| a != b));
+    }
+}